@@ -0,0 +1,73 @@
+use crate::db::sqlite::{BUSY_TIMEOUT, blob_to_embedding, embedding_to_blob};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Таблица кеша embedding: вектор по модели и нормализованному тексту
+pub struct EmbeddingCacheTable {
+    conn: Connection,
+}
+
+impl EmbeddingCacheTable {
+    /// Открытие таблицы в базе `path` и удаление записей старше `ttl_secs`
+    pub fn open(path: &str, ttl_secs: u64) -> Result<Self, anyhow::Error> {
+        let conn = Connection::open(path)?;
+        // Второе соединение к той же базе, что и `Database`: ждем блокировку, а не падаем
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS embedding_cache (
+                model TEXT NOT NULL,
+                text TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (model, text)
+            )",
+            [],
+        )?;
+        conn.execute(
+            "DELETE FROM embedding_cache WHERE created_at < ?1",
+            params![now_secs().saturating_sub(ttl_secs) as i64],
+        )?;
+        Ok(EmbeddingCacheTable { conn })
+    }
+
+    /// Вектор, сохраненный не раньше `ttl_secs` секунд назад, и его возраст в секундах
+    pub fn get(
+        &self,
+        model: &str,
+        text: &str,
+        ttl_secs: u64,
+    ) -> Result<Option<(Vec<f32>, u64)>, anyhow::Error> {
+        let now = now_secs();
+        let row: Option<(Vec<u8>, i64)> = self
+            .conn
+            .query_row(
+                "SELECT embedding, created_at FROM embedding_cache
+                 WHERE model = ?1 AND text = ?2 AND created_at >= ?3",
+                params![model, text, now.saturating_sub(ttl_secs) as i64],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(row.map(|(blob, created_at)| {
+            (
+                blob_to_embedding(&blob),
+                now.saturating_sub(created_at as u64),
+            )
+        }))
+    }
+
+    pub fn put(&self, model: &str, text: &str, embedding: &[f32]) -> Result<(), anyhow::Error> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO embedding_cache (model, text, embedding, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![model, text, embedding_to_blob(embedding), now_secs() as i64],
+        )?;
+        Ok(())
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs()
+}
@@ -1,7 +1,49 @@
-use crate::db::model::Record;
+use crate::db::model::{Record, RecordCategory, RecordFilter, RecordInfo};
 use anyhow::Result;
 
 pub trait DB {
     /// Получение всех записей из базы данных
     fn get_all(&self) -> Result<Vec<Record>>;
+
+    /// Получение записи по id
+    fn get(&self, id: i64) -> Result<Option<Record>>;
+
+    /// Записи по фильтру без векторов, по возрастанию id: с `offset`, не больше `limit`
+    fn list(
+        &self,
+        filter: &RecordFilter,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<RecordInfo>>;
+
+    /// Количество записей по фильтру
+    fn count(&self, filter: &RecordFilter) -> Result<usize>;
+
+    /// id записи с таким именем и типом
+    fn find_id(&self, name: &str, record_type: &RecordCategory) -> Result<Option<i64>>;
+
+    /// Добавление новой записи, возвращает id
+    fn insert(&self, record: &Record) -> Result<i64>;
+
+    /// Добавление записи или обновление существующей с тем же именем и типом, возвращает id
+    fn upsert(&self, record: &Record) -> Result<i64>;
+
+    /// Добавление или обновление всех записей одной транзакцией: при ошибке
+    /// не сохраняется ни одна. Возвращает id в порядке записей
+    fn upsert_all(&self, records: &[Record]) -> Result<Vec<i64>>;
+
+    /// Обновление записи по id. `false`, если записи с таким id нет
+    fn update(&self, record: &Record) -> Result<bool>;
+
+    /// Пометка записи как исключенной из реестра. `false`, если записи с таким id нет
+    fn mark_removed(&self, id: i64) -> Result<bool>;
+
+    /// Возврат исключенной записи в реестр. `false`, если записи с таким id нет
+    fn restore(&self, id: i64) -> Result<bool>;
+
+    /// Полное удаление записи. `false`, если записи с таким id нет
+    fn delete(&self, id: i64) -> Result<bool>;
+
+    /// Размерность большинства сохраненных embedding. `None`, если записей нет
+    fn embedding_dim(&self) -> Result<Option<usize>>;
 }
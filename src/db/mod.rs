@@ -1,3 +1,4 @@
+pub mod embedding_cache;
 pub mod interface;
 pub mod model;
 pub mod sqlite;
@@ -1,9 +1,232 @@
-use serde::Deserialize;
+use crate::error::model::ServiceError;
+use serde::{Deserialize, Serialize};
+use strsim::levenshtein;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Record {
+    /// id строки в таблице `records`, 0 – запись еще не сохранена
+    #[serde(default)]
+    pub id: i64,
     pub name: String,
-    pub record_type: String,
-    pub embedding: Vec<f32>,
+    pub record_type: RecordCategory,
+    /// `None`, если в имени нет русских букв: такая запись, как и псевдоним
+    /// латиницей, находится только по написанию
+    pub embedding: Option<Vec<f32>>,
     pub is_removed: bool,
+    #[serde(default)]
+    pub aliases: Vec<Alias>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Вид реестра записи. В API и базе данных – строкой: `ino`, `nezh`, `extremist`,
+/// `terrorist`. Другие значения сохраняются в `Other` как есть
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum RecordCategory {
+    /// Реестр иностранных агентов
+    #[default]
+    ForeignAgent,
+    /// Перечень нежелательных организаций
+    Undesirable,
+    /// Перечень экстремистских организаций
+    Extremist,
+    /// Перечень террористических организаций
+    Terrorist,
+    Other(String),
+}
+
+/// Серьезность упоминания записи без пометки, по возрастанию
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl RecordCategory {
+    pub const KNOWN: [RecordCategory; 4] = [
+        RecordCategory::ForeignAgent,
+        RecordCategory::Undesirable,
+        RecordCategory::Extremist,
+        RecordCategory::Terrorist,
+    ];
+
+    pub fn code(&self) -> &str {
+        match self {
+            RecordCategory::ForeignAgent => "ino",
+            RecordCategory::Undesirable => "nezh",
+            RecordCategory::Extremist => "extremist",
+            RecordCategory::Terrorist => "terrorist",
+            RecordCategory::Other(code) => code,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            RecordCategory::ForeignAgent => Severity::Medium,
+            RecordCategory::Undesirable => Severity::High,
+            RecordCategory::Extremist | RecordCategory::Terrorist => Severity::Critical,
+            RecordCategory::Other(_) => Severity::Low,
+        }
+    }
+
+    /// Правовой статус для людей: как он называется в законе
+    pub fn legal_label(&self) -> &str {
+        match self {
+            RecordCategory::ForeignAgent => "Иностранный агент",
+            RecordCategory::Undesirable => "Нежелательная организация",
+            RecordCategory::Extremist => "Экстремистская организация, деятельность запрещена в РФ",
+            RecordCategory::Terrorist => {
+                "Террористическая организация, деятельность запрещена в РФ"
+            }
+            RecordCategory::Other(code) => code,
+        }
+    }
+
+    /// Проверка типа из импорта и `/add`. Свой тип допускается, если это
+    /// латинский идентификатор и он не похож на опечатку в известном типе
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        let RecordCategory::Other(code) = self else {
+            return Ok(());
+        };
+        if code.is_empty()
+            || !code
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(ServiceError::InvalidInput(format!(
+                "Недопустимый тип записи «{code}»: нужны строчные латинские буквы, цифры и _"
+            )));
+        }
+        if let Some(known) = Self::KNOWN
+            .iter()
+            .find(|k| levenshtein(code, k.code()) <= MAX_TYPO_DISTANCE)
+        {
+            return Err(ServiceError::InvalidInput(format!(
+                "Неизвестный тип записи «{code}», возможно «{}»",
+                known.code()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Свой тип на таком расстоянии от известного считается опечаткой
+const MAX_TYPO_DISTANCE: usize = 2;
+
+impl From<String> for RecordCategory {
+    fn from(code: String) -> Self {
+        let code = code.trim().to_lowercase();
+        Self::KNOWN
+            .into_iter()
+            .find(|k| k.code() == code)
+            .unwrap_or(RecordCategory::Other(code))
+    }
+}
+
+impl From<RecordCategory> for String {
+    fn from(category: RecordCategory) -> Self {
+        category.code().to_string()
+    }
+}
+
+impl std::fmt::Display for RecordCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Псевдоним записи реестра (`Иванов Иван "Ваня Рок"` -> `Ваня Рок`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Alias {
+    pub name: String,
+    /// Нет для псевдонимов без русских букв – они проверяются только по написанию
+    pub embedding: Option<Vec<f32>>,
+}
+
+/// Отбор записей для `DB::list()`. `None` – без ограничения
+#[derive(Debug, Default)]
+pub struct RecordFilter {
+    pub record_type: Option<RecordCategory>,
+    pub is_removed: Option<bool>,
+}
+
+/// Запись реестра для ответов API – без векторов
+#[derive(Debug, Serialize)]
+pub struct RecordInfo {
+    pub id: i64,
+    pub name: String,
+    pub record_type: RecordCategory,
+    pub is_removed: bool,
+    pub aliases: Vec<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+impl From<&Record> for RecordInfo {
+    fn from(r: &Record) -> Self {
+        RecordInfo {
+            id: r.id,
+            name: r.name.clone(),
+            record_type: r.record_type.clone(),
+            is_removed: r.is_removed,
+            aliases: r.aliases.iter().map(|a| a.name.clone()).collect(),
+            created_at: r.created_at.clone(),
+            updated_at: r.updated_at.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(code: &str) -> String {
+        match RecordCategory::from(code.to_string()).validate() {
+            Err(ServiceError::InvalidInput(message)) => message,
+            other => panic!("{code}: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn custom_types_are_validated() {
+        for code in ["ino", "NEZH", "sanctions", "list_2024"] {
+            assert!(
+                RecordCategory::from(code.to_string()).validate().is_ok(),
+                "{code}"
+            );
+        }
+        // Опечатки в известных типах – с подсказкой
+        assert!(error("inno").ends_with("возможно «ino»"));
+        assert!(error("terorist").ends_with("возможно «terrorist»"));
+        assert!(error("nez").ends_with("возможно «nezh»"));
+        // Не идентификатор
+        for code in ["", "  ", "иноагент", "foreign-agent", "a b"] {
+            assert!(error(code).starts_with("Недопустимый тип записи"), "{code}");
+        }
+    }
+
+    #[test]
+    fn category_is_serialized_as_code() {
+        for category in RecordCategory::KNOWN
+            .into_iter()
+            .chain([RecordCategory::Other("sanctions".to_string())])
+        {
+            let json = serde_json::to_string(&category).unwrap();
+            assert_eq!(json, format!("\"{}\"", category.code()));
+            assert_eq!(
+                serde_json::from_str::<RecordCategory>(&json).unwrap(),
+                category
+            );
+        }
+        assert_eq!(
+            serde_json::from_str::<RecordCategory>("\" Extremist \"").unwrap(),
+            RecordCategory::Extremist
+        );
+    }
 }
@@ -1,6 +1,12 @@
 use crate::db::interface::DB;
-use crate::db::model::Record;
-use rusqlite::{Connection, Result};
+use crate::db::model::{Alias, Record, RecordCategory, RecordFilter, RecordInfo};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OptionalExtension, Result, params, params_from_iter};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Сколько ждать блокировку базы, занятой другим соединением
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct Database {
     conn: Connection,
@@ -9,6 +15,7 @@ pub struct Database {
 impl Database {
     pub fn new(path: &str) -> Result<Self> {
         let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         conn.execute(
             "CREATE TABLE IF NOT EXISTS records (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -19,39 +26,491 @@ impl Database {
             )",
             [],
         )?;
-        Ok(Self { conn })
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS record_aliases (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                record_id INTEGER NOT NULL REFERENCES records(id) ON DELETE CASCADE,
+                alias TEXT NOT NULL,
+                embedding BLOB
+            )",
+            [],
+        )?;
+        let db = Self { conn };
+        db.add_column_if_missing("records", "created_at", "TEXT")?;
+        db.add_column_if_missing("records", "updated_at", "TEXT")?;
+        Ok(db)
+    }
+
+    /// Миграция баз, созданных до появления колонки
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({table})"))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<String>>>()?
+            .iter()
+            .any(|c| c == column);
+        if !exists {
+            self.conn.execute(
+                &format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"),
+                [],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Псевдонимы одной записи
+    fn get_aliases(&self, record_id: i64) -> Result<Vec<Alias>, anyhow::Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT alias, embedding FROM record_aliases WHERE record_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![record_id], |row| {
+            let name: String = row.get(0)?;
+            let blob: Option<Vec<u8>> = row.get(1)?;
+            Ok(Alias {
+                name,
+                embedding: blob.map(|b| blob_to_embedding(&b)),
+            })
+        })?;
+
+        let mut aliases = Vec::new();
+        for alias in rows {
+            aliases.push(alias?);
+        }
+        Ok(aliases)
+    }
+
+    /// Псевдонимы всех записей, сгруппированные по id записи
+    fn get_all_aliases(&self) -> Result<HashMap<i64, Vec<Alias>>, anyhow::Error> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT record_id, alias, embedding FROM record_aliases ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            let record_id: i64 = row.get(0)?;
+            let name: String = row.get(1)?;
+            let blob: Option<Vec<u8>> = row.get(2)?;
+            Ok((
+                record_id,
+                Alias {
+                    name,
+                    embedding: blob.map(|b| blob_to_embedding(&b)),
+                },
+            ))
+        })?;
+
+        let mut aliases: HashMap<i64, Vec<Alias>> = HashMap::new();
+        for row in rows {
+            let (record_id, alias) = row?;
+            aliases.entry(record_id).or_default().push(alias);
+        }
+        Ok(aliases)
+    }
+
+    /// Замена псевдонимов записи на новый список
+    fn replace_aliases(&self, record_id: i64, aliases: &[Alias]) -> Result<(), anyhow::Error> {
+        self.conn.execute(
+            "DELETE FROM record_aliases WHERE record_id = ?1",
+            params![record_id],
+        )?;
+        for alias in aliases {
+            self.conn.execute(
+                "INSERT INTO record_aliases (record_id, alias, embedding) VALUES (?1, ?2, ?3)",
+                params![
+                    record_id,
+                    alias.name,
+                    alias.embedding.as_deref().map(embedding_to_blob)
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Выполнение `f` в транзакции: при ошибке не сохраняется ничего.
+    /// Внутри уже начатой транзакции `f` выполняется в ней
+    fn atomic<R>(&self, f: impl FnOnce() -> Result<R, anyhow::Error>) -> Result<R, anyhow::Error> {
+        if !self.conn.is_autocommit() {
+            return f();
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let result = f()?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Условие `WHERE` и его параметры для фильтра
+    fn filter_clause(filter: &RecordFilter) -> (String, Vec<Value>) {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(record_type) = &filter.record_type {
+            conditions.push("type = ?");
+            values.push(Value::Text(record_type.code().to_string()));
+        }
+        if let Some(is_removed) = filter.is_removed {
+            conditions.push("is_removed = ?");
+            values.push(Value::Integer(is_removed.into()));
+        }
+        if conditions.is_empty() {
+            return (String::new(), values);
+        }
+        (format!("WHERE {}", conditions.join(" AND ")), values)
+    }
+
+    fn set_removed(&self, id: i64, is_removed: bool) -> Result<bool, anyhow::Error> {
+        let updated = self.conn.execute(
+            "UPDATE records SET is_removed = ?1, updated_at = datetime('now') WHERE id = ?2",
+            params![is_removed, id],
+        )?;
+        Ok(updated > 0)
     }
 }
 
 impl DB for Database {
     /// Чтение всех записей из базы данныз
     fn get_all(&self) -> Result<Vec<Record>, anyhow::Error> {
+        let mut aliases = self.get_all_aliases()?;
         let mut stmt = self
             .conn
-            .prepare("SELECT name, type, embedding, is_removed FROM records")?;
-        let rows = stmt.query_map([], |row| {
-            let name: String = row.get(0)?;
-            let record_type: String = row.get(1)?;
-            let blob: Vec<u8> = row.get(2)?;
-            let is_removed: i32 = row.get(3)?;
+            .prepare(&format!("SELECT {RECORD_COLUMNS} FROM records"))?;
+        let rows = stmt.query_map([], row_to_record)?;
 
-            let embedding = blob
-                .chunks_exact(4)
-                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
-                .collect();
+        let mut records = Vec::new();
+        for rec in rows {
+            let mut rec = rec?;
+            rec.aliases = aliases.remove(&rec.id).unwrap_or_default();
+            records.push(rec);
+        }
+        Ok(records)
+    }
 
-            Ok(Record {
-                name,
-                record_type,
-                embedding,
+    /// Чтение записи по id
+    fn get(&self, id: i64) -> Result<Option<Record>, anyhow::Error> {
+        let record = self
+            .conn
+            .query_row(
+                &format!("SELECT {RECORD_COLUMNS} FROM records WHERE id = ?1"),
+                params![id],
+                row_to_record,
+            )
+            .optional()?;
+
+        match record {
+            Some(mut rec) => {
+                rec.aliases = self.get_aliases(id)?;
+                Ok(Some(rec))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Страница записей без чтения embedding, псевдонимы – только для записей страницы
+    fn list(
+        &self,
+        filter: &RecordFilter,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<RecordInfo>, anyhow::Error> {
+        let (clause, mut values) = Self::filter_clause(filter);
+        // В SQLite отрицательный LIMIT – без ограничения
+        let limit = limit.map_or(-1, |l| i64::try_from(l).unwrap_or(i64::MAX));
+        values.push(Value::Integer(limit));
+        values.push(Value::Integer(i64::try_from(offset).unwrap_or(i64::MAX)));
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, name, type, is_removed, created_at, updated_at FROM records
+             {clause} ORDER BY id LIMIT ? OFFSET ?"
+        ))?;
+        let rows = stmt.query_map(params_from_iter(values), |row| {
+            let is_removed: i32 = row.get(3)?;
+            Ok(RecordInfo {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                record_type: RecordCategory::from(row.get::<_, String>(2)?),
                 is_removed: is_removed != 0,
+                aliases: Vec::new(),
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
             })
         })?;
 
         let mut records = Vec::new();
         for rec in rows {
-            records.push(rec?);
+            let mut rec = rec?;
+            rec.aliases = self
+                .get_aliases(rec.id)?
+                .into_iter()
+                .map(|a| a.name)
+                .collect();
+            records.push(rec);
         }
         Ok(records)
     }
+
+    fn count(&self, filter: &RecordFilter) -> Result<usize, anyhow::Error> {
+        let (clause, values) = Self::filter_clause(filter);
+        let count: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM records {clause}"),
+            params_from_iter(values),
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    fn find_id(
+        &self,
+        name: &str,
+        record_type: &RecordCategory,
+    ) -> Result<Option<i64>, anyhow::Error> {
+        let id = self
+            .conn
+            .query_row(
+                "SELECT id FROM records WHERE name = ?1 AND type = ?2",
+                params![name, record_type.code()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
+
+    /// Добавление новой записи, возвращает id
+    fn insert(&self, record: &Record) -> Result<i64, anyhow::Error> {
+        self.atomic(|| {
+            self.conn.execute(
+                "INSERT INTO records (name, type, embedding, is_removed, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, datetime('now'), datetime('now'))",
+                params![
+                    record.name,
+                    record.record_type.code(),
+                    record_blob(record),
+                    record.is_removed
+                ],
+            )?;
+            let id = self.conn.last_insert_rowid();
+            self.replace_aliases(id, &record.aliases)?;
+            Ok(id)
+        })
+    }
+
+    /// Добавление записи или обновление существующей с тем же именем и типом
+    fn upsert(&self, record: &Record) -> Result<i64, anyhow::Error> {
+        self.atomic(|| match self.find_id(&record.name, &record.record_type)? {
+            Some(id) => {
+                self.conn.execute(
+                    "UPDATE records SET embedding = ?1, is_removed = ?2, updated_at = datetime('now')
+                     WHERE id = ?3",
+                    params![record_blob(record), record.is_removed, id],
+                )?;
+                self.replace_aliases(id, &record.aliases)?;
+                Ok(id)
+            }
+            None => self.insert(record),
+        })
+    }
+
+    /// Все записи через `upsert()` в одной транзакции
+    fn upsert_all(&self, records: &[Record]) -> Result<Vec<i64>, anyhow::Error> {
+        self.atomic(|| records.iter().map(|r| self.upsert(r)).collect())
+    }
+
+    /// Обновление имени, типа, embedding, флага и псевдонимов записи по id
+    fn update(&self, record: &Record) -> Result<bool, anyhow::Error> {
+        self.atomic(|| {
+            let updated = self.conn.execute(
+                "UPDATE records SET name = ?1, type = ?2, embedding = ?3, is_removed = ?4,
+                    updated_at = datetime('now')
+                 WHERE id = ?5",
+                params![
+                    record.name,
+                    record.record_type.code(),
+                    record_blob(record),
+                    record.is_removed,
+                    record.id
+                ],
+            )?;
+            if updated == 0 {
+                return Ok(false);
+            }
+            self.replace_aliases(record.id, &record.aliases)?;
+            Ok(true)
+        })
+    }
+
+    /// Пометка записи как исключенной из реестра
+    fn mark_removed(&self, id: i64) -> Result<bool, anyhow::Error> {
+        self.set_removed(id, true)
+    }
+
+    /// Возврат исключенной записи в реестр
+    fn restore(&self, id: i64) -> Result<bool, anyhow::Error> {
+        self.set_removed(id, false)
+    }
+
+    /// Полное удаление записи из базы данных
+    fn delete(&self, id: i64) -> Result<bool, anyhow::Error> {
+        self.atomic(|| {
+            self.conn.execute(
+                "DELETE FROM record_aliases WHERE record_id = ?1",
+                params![id],
+            )?;
+            let deleted = self
+                .conn
+                .execute("DELETE FROM records WHERE id = ?1", params![id])?;
+            Ok(deleted > 0)
+        })
+    }
+
+    /// Самая частая длина embedding в байтах, деленная на размер f32
+    fn embedding_dim(&self) -> Result<Option<usize>, anyhow::Error> {
+        let dim: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT length(embedding) / 4 AS dim FROM records
+                 WHERE length(embedding) > 0
+                 GROUP BY dim ORDER BY COUNT(*) DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(dim.map(|d| d as usize))
+    }
+}
+
+const RECORD_COLUMNS: &str = "id, name, type, embedding, is_removed, created_at, updated_at";
+
+fn row_to_record(row: &rusqlite::Row) -> Result<Record> {
+    let blob: Vec<u8> = row.get(3)?;
+    let is_removed: i32 = row.get(4)?;
+    Ok(Record {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        record_type: RecordCategory::from(row.get::<_, String>(2)?),
+        embedding: (!blob.is_empty()).then(|| blob_to_embedding(&blob)),
+        is_removed: is_removed != 0,
+        aliases: Vec::new(),
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+/// Колонка `embedding` не допускает NULL, поэтому запись без embedding
+/// хранится с пустым BLOB
+fn record_blob(record: &Record) -> Vec<u8> {
+    record
+        .embedding
+        .as_deref()
+        .map(embedding_to_blob)
+        .unwrap_or_default()
+}
+
+pub fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+pub fn blob_to_embedding(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, record_type: RecordCategory, is_removed: bool) -> Record {
+        Record {
+            id: 0,
+            name: name.to_string(),
+            record_type,
+            embedding: Some(vec![0.5; 4]),
+            is_removed,
+            aliases: vec![Alias {
+                name: format!("{name} псевдоним"),
+                embedding: None,
+            }],
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn pages_are_read_in_sql() {
+        let db = Database::new(":memory:").unwrap();
+        for i in 0..5 {
+            db.insert(&record(
+                &format!("Иноагент {i}"),
+                RecordCategory::ForeignAgent,
+                i == 4,
+            ))
+            .unwrap();
+        }
+        db.insert(&record("Организация", RecordCategory::Undesirable, false))
+            .unwrap();
+
+        let ino = RecordFilter {
+            record_type: Some(RecordCategory::ForeignAgent),
+            is_removed: Some(false),
+        };
+        assert_eq!(db.count(&ino).unwrap(), 4);
+        assert_eq!(db.count(&RecordFilter::default()).unwrap(), 6);
+
+        let page = db.list(&ino, 2, Some(10)).unwrap();
+        let names: Vec<&str> = page.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["Иноагент 2", "Иноагент 3"]);
+        assert_eq!(page[0].aliases, ["Иноагент 2 псевдоним"]);
+        assert!(db.list(&ino, usize::MAX, Some(10)).unwrap().is_empty());
+        assert_eq!(db.list(&RecordFilter::default(), 0, None).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn records_are_found_by_name_and_type() {
+        let db = Database::new(":memory:").unwrap();
+        let id = db
+            .insert(&record("Медуза", RecordCategory::ForeignAgent, false))
+            .unwrap();
+        assert_eq!(
+            db.find_id("Медуза", &RecordCategory::ForeignAgent).unwrap(),
+            Some(id)
+        );
+        assert_eq!(
+            db.find_id("Медуза", &RecordCategory::Undesirable).unwrap(),
+            None
+        );
+        assert_eq!(
+            db.upsert(&record("Медуза", RecordCategory::ForeignAgent, true))
+                .unwrap(),
+            id
+        );
+    }
+
+    #[test]
+    fn records_without_embedding_are_stored() {
+        let db = Database::new(":memory:").unwrap();
+        db.insert(&record("Медуза", RecordCategory::ForeignAgent, false))
+            .unwrap();
+        let latin = Record {
+            embedding: None,
+            ..record("Meduza", RecordCategory::ForeignAgent, false)
+        };
+        let id = db.insert(&latin).unwrap();
+        assert!(db.get(id).unwrap().unwrap().embedding.is_none());
+        assert_eq!(db.embedding_dim().unwrap(), Some(4));
+    }
+
+    #[test]
+    fn failed_batch_is_rolled_back() {
+        let db = Database::new(":memory:").unwrap();
+        db.conn
+            .execute_batch(
+                "CREATE TRIGGER reject BEFORE INSERT ON records WHEN NEW.name = 'Ошибка'
+                 BEGIN SELECT RAISE(ABORT, 'rejected'); END",
+            )
+            .unwrap();
+        let records = [
+            record("Медуза", RecordCategory::ForeignAgent, false),
+            record("Ошибка", RecordCategory::ForeignAgent, false),
+        ];
+        assert!(db.upsert_all(&records).is_err());
+        assert_eq!(db.count(&RecordFilter::default()).unwrap(), 0);
+        assert!(db.conn.is_autocommit());
+
+        let ids = db.upsert_all(&records[..1]).unwrap();
+        assert_eq!(db.get(ids[0]).unwrap().unwrap().aliases.len(), 1);
+    }
 }
@@ -0,0 +1,42 @@
+use crate::embedding::interface::Embedding;
+use crate::embedding::local::LocalEmbedding;
+use crate::embedding::model::{Embeddings, Response};
+use crate::embedding::vectorize::YandexEmbedding;
+use crate::error::model::ServiceError;
+
+/// Способ векторизации, выбранный в конфигурации (`EMBEDDING_BACKEND`)
+#[derive(Clone)]
+pub enum EmbeddingBackend {
+    Yandex(YandexEmbedding),
+    Local(LocalEmbedding),
+}
+
+impl Embedding for EmbeddingBackend {
+    async fn get_embedding(&self, text: &str) -> Result<Response, ServiceError> {
+        match self {
+            EmbeddingBackend::Yandex(e) => e.get_embedding(text).await,
+            EmbeddingBackend::Local(e) => e.get_embedding(text).await,
+        }
+    }
+
+    async fn get_embeddings(&self, texts: &[String]) -> Embeddings {
+        match self {
+            EmbeddingBackend::Yandex(e) => e.get_embeddings(texts).await,
+            EmbeddingBackend::Local(e) => e.get_embeddings(texts).await,
+        }
+    }
+
+    fn dimension(&self) -> usize {
+        match self {
+            EmbeddingBackend::Yandex(e) => e.dimension(),
+            EmbeddingBackend::Local(e) => e.dimension(),
+        }
+    }
+
+    fn model(&self) -> String {
+        match self {
+            EmbeddingBackend::Yandex(e) => e.model(),
+            EmbeddingBackend::Local(e) => e.model(),
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use crate::db::embedding_cache::EmbeddingCacheTable;
+use crate::embedding::interface::Embedding;
+use crate::embedding::model::{Embeddings, Response};
+use crate::error::model::ServiceError;
+use crate::utils::funcs::keep_russian_and_dot;
+use lru::LruCache;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Кеш embedding поверх любой реализации `Embedding`.
+///
+/// Ключ – модель и текст после `keep_russian_and_dot` в нижнем регистре, то есть
+/// ровно то, что уходит в модель. Сначала проверяется LRU в памяти, затем
+/// таблица SQLite, если она подключена. Клоны разделяют один кеш и счетчики
+#[derive(Clone)]
+pub struct CachedEmbedding<E: Embedding> {
+    inner: E,
+    model: String,
+    ttl: Duration,
+    state: Arc<CacheState>,
+}
+
+struct CacheState {
+    memory: Mutex<LruCache<String, (Vec<f32>, Instant)>>,
+    table: Option<Mutex<EmbeddingCacheTable>>,
+    memory_hits: AtomicU64,
+    sqlite_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub memory_hits: u64,
+    pub sqlite_hits: u64,
+    pub misses: u64,
+    /// Записей в LRU в памяти
+    pub memory_entries: usize,
+    pub sqlite_enabled: bool,
+}
+
+impl<E: Embedding> CachedEmbedding<E> {
+    /// `capacity` – размер LRU в памяти, `ttl` – срок жизни записи в памяти и в SQLite
+    pub fn new(
+        inner: E,
+        capacity: NonZeroUsize,
+        ttl: Duration,
+        table: Option<EmbeddingCacheTable>,
+    ) -> Self {
+        CachedEmbedding {
+            model: inner.model(),
+            inner,
+            ttl,
+            state: Arc::new(CacheState {
+                memory: Mutex::new(LruCache::new(capacity)),
+                table: table.map(Mutex::new),
+                memory_hits: AtomicU64::new(0),
+                sqlite_hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            memory_hits: self.state.memory_hits.load(Ordering::Relaxed),
+            sqlite_hits: self.state.sqlite_hits.load(Ordering::Relaxed),
+            misses: self.state.misses.load(Ordering::Relaxed),
+            memory_entries: self.state.memory.lock().unwrap().len(),
+            sqlite_enabled: self.state.table.is_some(),
+        }
+    }
+
+    /// Запись из LRU в памяти, иначе из SQLite. Запрос к SQLite выполняется
+    /// в `spawn_blocking`, чтобы не занимать поток async runtime
+    async fn get_cached(&self, key: &str) -> Option<Vec<f32>> {
+        {
+            let mut memory = self.state.memory.lock().unwrap();
+            match memory.get(key) {
+                Some((embedding, created)) if created.elapsed() < self.ttl => {
+                    self.state.memory_hits.fetch_add(1, Ordering::Relaxed);
+                    return Some(embedding.clone());
+                }
+                Some(_) => {
+                    memory.pop(key);
+                }
+                None => {}
+            }
+        }
+
+        self.state.table.as_ref()?;
+        let state = self.state.clone();
+        let model = self.model.clone();
+        let text = key.to_string();
+        let ttl = self.ttl.as_secs();
+        let (found, age) = tokio::task::spawn_blocking(move || {
+            let table = state.table.as_ref()?;
+            table
+                .lock()
+                .unwrap()
+                .get(&model, &text, ttl)
+                .unwrap_or_else(|e| {
+                    eprintln!("Embedding cache read failed: {e}");
+                    None
+                })
+        })
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("Embedding cache read failed: {e}");
+            None
+        })?;
+        self.state.sqlite_hits.fetch_add(1, Ordering::Relaxed);
+        // В памяти запись живет столько же, сколько ей осталось в SQLite
+        let created = Instant::now()
+            .checked_sub(Duration::from_secs(age))
+            .unwrap_or_else(Instant::now);
+        self.state
+            .memory
+            .lock()
+            .unwrap()
+            .put(key.to_string(), (found.clone(), created));
+        Some(found)
+    }
+
+    /// Запись в LRU сразу, в SQLite – одной фоновой задачей на весь набор
+    fn put_cached(&self, entries: Vec<(String, Vec<f32>)>) {
+        {
+            let mut memory = self.state.memory.lock().unwrap();
+            for (key, embedding) in &entries {
+                memory.put(key.clone(), (embedding.clone(), Instant::now()));
+            }
+        }
+        if self.state.table.is_none() || entries.is_empty() {
+            return;
+        }
+        let state = self.state.clone();
+        let model = self.model.clone();
+        tokio::task::spawn_blocking(move || {
+            let Some(table) = &state.table else {
+                return;
+            };
+            let table = table.lock().unwrap();
+            for (key, embedding) in entries {
+                if let Err(e) = table.put(&model, &key, &embedding) {
+                    eprintln!("Embedding cache write failed: {e}");
+                }
+            }
+        });
+    }
+}
+
+impl<E: Embedding> Embedding for CachedEmbedding<E> {
+    /// Embedding из кеша или от модели. В кеш попадают только успешные ответы
+    async fn get_embedding(&self, text: &str) -> Result<Response, ServiceError> {
+        let key = keep_russian_and_dot(text).to_lowercase();
+        if let Some(embedding) = self.get_cached(&key).await {
+            return Ok(Response {
+                embedding: Some(embedding),
+                error: None,
+            });
+        }
+
+        self.state.misses.fetch_add(1, Ordering::Relaxed);
+        let response = self.inner.get_embedding(text).await?;
+        if let Some(embedding) = &response.embedding
+            && response.error.is_none()
+        {
+            self.put_cached(vec![(key, embedding.clone())]);
+        }
+        Ok(response)
+    }
+
+    /// Из модели запрашиваются только тексты, которых нет в кеше
+    async fn get_embeddings(&self, texts: &[String]) -> Embeddings {
+        let mut embeddings = Embeddings::new();
+        // Тексты с одинаковым ключом кеша запрашиваются один раз
+        let mut missed: HashMap<String, Vec<String>> = HashMap::new();
+
+        for text in texts {
+            if embeddings.contains_key(text) {
+                continue;
+            }
+            let key = keep_russian_and_dot(text).to_lowercase();
+            if let Some(embedding) = self.get_cached(&key).await {
+                embeddings.insert(text.clone(), Ok(embedding));
+                continue;
+            }
+            let same_key = missed.entry(key).or_default();
+            if !same_key.contains(text) {
+                same_key.push(text.clone());
+            }
+        }
+        if missed.is_empty() {
+            return embeddings;
+        }
+
+        let requested: Vec<String> = missed.values().map(|t| t[0].clone()).collect();
+        self.state
+            .misses
+            .fetch_add(requested.len() as u64, Ordering::Relaxed);
+        let mut fetched = self.inner.get_embeddings(&requested).await;
+        let mut fresh = Vec::new();
+
+        for (key, same_key) in missed {
+            let result = fetched.remove(&same_key[0]).unwrap_or_else(|| {
+                Err(ServiceError::Internal(
+                    "Unknown embedding error".to_string(),
+                ))
+            });
+            match result {
+                Ok(embedding) => {
+                    for text in same_key {
+                        embeddings.insert(text, Ok(embedding.clone()));
+                    }
+                    fresh.push((key, embedding));
+                }
+                Err(e) => {
+                    for text in same_key {
+                        embeddings.insert(text, Err(e.clone()));
+                    }
+                }
+            }
+        }
+        self.put_cached(fresh);
+        embeddings
+    }
+
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::embedding_to_blob;
+    use crate::embedding::local::LocalEmbedding;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// `LocalEmbedding`, у которого первая координата – номер запроса к модели:
+    /// по ней видно, из какого запроса взят вектор
+    #[derive(Clone)]
+    struct Numbered {
+        inner: LocalEmbedding,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Numbered {
+        fn new() -> Self {
+            Numbered {
+                inner: LocalEmbedding::new(8),
+                calls: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl Embedding for Numbered {
+        async fn get_embedding(&self, text: &str) -> Result<Response, ServiceError> {
+            let call = self.calls.fetch_add(1, Ordering::Relaxed) + 1;
+            let mut response = self.inner.get_embedding(text).await?;
+            if let Some(embedding) = &mut response.embedding {
+                embedding[0] = call as f32;
+            }
+            Ok(response)
+        }
+
+        fn dimension(&self) -> usize {
+            self.inner.dimension()
+        }
+
+        fn model(&self) -> String {
+            self.inner.model()
+        }
+    }
+
+    fn cached(
+        capacity: usize,
+        ttl: Duration,
+        table: Option<EmbeddingCacheTable>,
+    ) -> CachedEmbedding<Numbered> {
+        CachedEmbedding::new(
+            Numbered::new(),
+            NonZeroUsize::new(capacity).unwrap(),
+            ttl,
+            table,
+        )
+    }
+
+    /// Номер запроса к модели, из которого взят embedding `text`
+    async fn call_of(cache: &CachedEmbedding<Numbered>, text: &str) -> f32 {
+        let embedding = cache.get_embedding(text).await.unwrap().into_result();
+        embedding.unwrap()[0]
+    }
+
+    fn counters(cache: &CachedEmbedding<Numbered>) -> (u64, u64, u64, usize) {
+        let stats = cache.stats();
+        (
+            stats.memory_hits,
+            stats.sqlite_hits,
+            stats.misses,
+            stats.memory_entries,
+        )
+    }
+
+    /// База кеша во временном каталоге, `name` различает тесты
+    fn temp_db(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "embedding-cache-{name}-{}.sqlite",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Ждет фоновую запись в SQLite: embedding `key` из запроса `call`
+    async fn wait_saved(path: &str, key: &str, call: f32) {
+        let table = EmbeddingCacheTable::open(path, 3600).unwrap();
+        let model = Numbered::new().model();
+        for _ in 0..100 {
+            if let Some((embedding, _)) = table.get(&model, key, 3600).unwrap()
+                && embedding[0] == call
+            {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("{key} не записан в SQLite");
+    }
+
+    #[tokio::test]
+    async fn memory_hits_and_expired_entries() {
+        let cache = cached(4, Duration::from_millis(50), None);
+
+        assert_eq!(call_of(&cache, "Иван").await, 1.0);
+        // Тот же ключ после нормализации
+        assert_eq!(call_of(&cache, "ИВАН!").await, 1.0);
+        assert_eq!(counters(&cache), (1, 0, 1, 1));
+
+        // Просроченная запись запрашивается заново и заменяется
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(call_of(&cache, "Иван").await, 2.0);
+        assert_eq!(call_of(&cache, "Иван").await, 2.0);
+        assert_eq!(counters(&cache), (2, 0, 2, 1));
+
+        let texts = ["иван", "Петр", "петр"].map(String::from);
+        let embeddings = cache.get_embeddings(&texts).await;
+        let calls: Vec<f32> = texts
+            .iter()
+            .map(|t| embeddings[t].as_ref().unwrap()[0])
+            .collect();
+        assert_eq!(calls, [2.0, 3.0, 3.0]);
+        assert_eq!(counters(&cache), (3, 0, 3, 2));
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_is_evicted() {
+        let cache = cached(2, Duration::from_secs(3600), None);
+
+        call_of(&cache, "Анна").await;
+        call_of(&cache, "Борис").await;
+        // Анна использована позже Бориса, поэтому вытесняется Борис
+        call_of(&cache, "Анна").await;
+        call_of(&cache, "Виктор").await;
+        assert_eq!(counters(&cache), (1, 0, 3, 2));
+
+        assert_eq!(call_of(&cache, "Анна").await, 1.0);
+        assert_eq!(call_of(&cache, "Борис").await, 4.0);
+        assert_eq!(counters(&cache), (2, 0, 4, 2));
+    }
+
+    #[tokio::test]
+    async fn sqlite_keeps_entries_for_new_cache() {
+        let path = temp_db("reopen");
+        let ttl = Duration::from_secs(3600);
+
+        let cache = cached(
+            4,
+            ttl,
+            Some(EmbeddingCacheTable::open(&path, 3600).unwrap()),
+        );
+        assert_eq!(call_of(&cache, "Иван").await, 1.0);
+        wait_saved(&path, "иван", 1.0).await;
+        drop(cache);
+
+        let cache = cached(
+            4,
+            ttl,
+            Some(EmbeddingCacheTable::open(&path, 3600).unwrap()),
+        );
+        assert_eq!(call_of(&cache, "Иван").await, 1.0);
+        assert_eq!(counters(&cache), (0, 1, 0, 1));
+        // Запись из SQLite попадает в память
+        assert_eq!(call_of(&cache, "Иван").await, 1.0);
+        assert_eq!(counters(&cache), (1, 1, 0, 1));
+        assert_eq!(cache.inner.calls.load(Ordering::Relaxed), 0);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn expired_sqlite_entry_is_refetched() {
+        let path = temp_db("expired");
+        let table = EmbeddingCacheTable::open(&path, 60).unwrap();
+        // Запись старше срока жизни, сохраненная до открытия кеша
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            - 3600;
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute(
+                "INSERT INTO embedding_cache (model, text, embedding, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![
+                    Numbered::new().model(),
+                    "иван",
+                    embedding_to_blob(&[9.0; 8]),
+                    created_at as i64
+                ],
+            )
+            .unwrap();
+
+        let cache = cached(4, Duration::from_secs(60), Some(table));
+        assert_eq!(call_of(&cache, "Иван").await, 1.0);
+        assert_eq!(counters(&cache), (0, 0, 1, 1));
+        wait_saved(&path, "иван", 1.0).await;
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
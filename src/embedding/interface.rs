@@ -1,6 +1,32 @@
-use crate::embedding::model::Response;
+use crate::embedding::model::{Embeddings, Response};
+use crate::error::model::ServiceError;
 
 pub trait Embedding {
-    /// Получение векторного представления текста в виде 256-мерного массива f32
-    async fn get_embedding(&self, text: &str) -> Result<Response, reqwest::Error>;
+    /// Получение векторного представления текста в виде массива f32 размерности `dimension()`
+    async fn get_embedding(&self, text: &str) -> Result<Response, ServiceError>;
+
+    /// Получение embedding для набора текстов, например всех имен из одной статьи.
+    ///
+    /// Повторяющиеся тексты запрашиваются один раз. По умолчанию тексты
+    /// запрашиваются по очереди через `get_embedding()`
+    async fn get_embeddings(&self, texts: &[String]) -> Embeddings {
+        let mut embeddings = Embeddings::new();
+        for text in texts {
+            if embeddings.contains_key(text) {
+                continue;
+            }
+            let embedding = self
+                .get_embedding(text)
+                .await
+                .and_then(Response::into_result);
+            embeddings.insert(text.clone(), embedding);
+        }
+        embeddings
+    }
+
+    /// Размерность векторов модели
+    fn dimension(&self) -> usize;
+
+    /// Идентификатор модели: векторы разных моделей не смешиваются в кеше
+    fn model(&self) -> String;
 }
@@ -0,0 +1,131 @@
+use crate::embedding::interface::Embedding;
+use crate::embedding::model::Response;
+use crate::error::model::ServiceError;
+use crate::utils::funcs::keep_russian_and_dot;
+
+/// Длины символьных n-грамм, из которых складывается вектор
+const NGRAM_SIZES: [usize; 3] = [1, 2, 3];
+
+/// Локальная векторизация без сети: хеширование символьных n-грамм.
+///
+/// Каждая n-грамма нормализованного текста попадает в одну из `dim` координат
+/// по хешу FNV-1a, знак вклада тоже берется из хеша. Хеш не зависит от версии
+/// компилятора, поэтому векторы в базе остаются валидными между сборками
+#[derive(Clone)]
+pub struct LocalEmbedding {
+    dim: usize,
+}
+
+impl LocalEmbedding {
+    pub fn new(dim: usize) -> Self {
+        LocalEmbedding { dim }
+    }
+
+    fn vectorize(&self, text: &str) -> Vec<f32> {
+        // Границы слова отмечаются, чтобы начало и конец имени весили больше
+        let chars: Vec<char> = format!("^{text}$").chars().collect();
+        let mut vector = vec![0.0f32; self.dim];
+
+        for n in NGRAM_SIZES {
+            for ngram in chars.windows(n) {
+                let hash = fnv1a(ngram);
+                let pos = (hash % self.dim as u64) as usize;
+                if hash >> 63 == 0 {
+                    vector[pos] += 1.0;
+                } else {
+                    vector[pos] -= 1.0;
+                }
+            }
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+impl Embedding for LocalEmbedding {
+    /// Получение векторного представления текста с той же нормализацией, что и у Yandex
+    async fn get_embedding(&self, text: &str) -> Result<Response, ServiceError> {
+        let sub_text = keep_russian_and_dot(text).to_lowercase();
+        if sub_text.is_empty() {
+            return Err(ServiceError::InvalidInput(
+                "Текст не содержит русских букв".to_string(),
+            ));
+        }
+        Ok(Response {
+            embedding: Some(self.vectorize(&sub_text)),
+            error: None,
+        })
+    }
+
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+
+    fn model(&self) -> String {
+        format!("local-ngram/{}", self.dim)
+    }
+}
+
+fn fnv1a(chars: &[char]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for c in chars {
+        for b in (*c as u32).to_le_bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embed(e: &LocalEmbedding, text: &str) -> Vec<f32> {
+        futures::executor::block_on(e.get_embedding(text))
+            .and_then(Response::into_result)
+            .unwrap()
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn same_text_gives_same_unit_vector() {
+        let e = LocalEmbedding::new(256);
+        let a = embed(&e, "Иванов Иван Иванович");
+        assert_eq!(
+            a,
+            embed(&LocalEmbedding::new(256), "иванов  ИВАН иванович!")
+        );
+        assert_eq!(a.len(), e.dimension());
+        assert!((cosine(&a, &a) - 1.0).abs() < 1e-5);
+        assert_eq!(embed(&LocalEmbedding::new(64), "Иванов").len(), 64);
+    }
+
+    #[test]
+    fn hash_does_not_depend_on_build() {
+        assert_eq!(fnv1a(&['а']), 0x2ad4c9fa26d25039);
+    }
+
+    #[test]
+    fn similar_names_are_closer() {
+        let e = LocalEmbedding::new(256);
+        let name = embed(&e, "Навальный Алексей");
+        let typo = embed(&e, "Навальны Алексей");
+        let other = embed(&e, "Смирнов Сергей");
+        assert!(cosine(&name, &typo) > cosine(&name, &other));
+    }
+
+    #[test]
+    fn text_without_russian_letters_is_rejected() {
+        let e = LocalEmbedding::new(16);
+        let result = futures::executor::block_on(e.get_embedding("Ivanov"));
+        assert!(matches!(result, Err(ServiceError::InvalidInput(_))));
+    }
+}
@@ -1,3 +1,6 @@
+pub mod backend;
+pub mod cache;
 pub mod interface;
+pub mod local;
 pub mod model;
 pub mod vectorize;
@@ -1,4 +1,30 @@
+use crate::error::model::ServiceError;
+use crate::resilience::model::{Failure, FailureKind};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Размерность векторов модели Yandex `text-search-query` – используется,
+/// если размерность не задана в конфигурации и в базе еще нет векторов
+pub const DEFAULT_EMBEDDING_DIM: usize = 256;
+
+/// Embedding или ошибка для каждого текста из набора
+pub type Embeddings = HashMap<String, Result<Vec<f32>, ServiceError>>;
+
+/// Сколько запросов к модели может выполняться одновременно при запросе набора текстов
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Признаки ошибок Yandex о превышении квоты и перегрузке (gRPC `RESOURCE_EXHAUSTED`,
+/// `UNAVAILABLE`) – ответ 503
+const UNAVAILABLE_ERRORS: [&str; 6] = [
+    "quota",
+    "resource_exhausted",
+    "resource exhausted",
+    "too many requests",
+    "unavailable",
+    "overload",
+];
+/// Признаки ошибок Yandex об истечении времени (gRPC `DEADLINE_EXCEEDED`) – ответ 504
+const TIMEOUT_ERRORS: [&str; 3] = ["deadline_exceeded", "deadline exceeded", "timeout"];
 
 #[derive(Serialize)]
 pub struct Request {
@@ -12,3 +38,71 @@ pub struct Response {
     pub embedding: Option<Vec<f32>>,
     pub error: Option<String>,
 }
+
+impl Response {
+    /// Ошибка Yandex из поля `error` как сбой попытки. Ответ приходит со статусом 200,
+    /// поэтому превышение квоты и перегрузку нужно распознать по тексту:
+    /// такие попытки повторяются и учитываются предохранителем
+    pub fn failure(&self) -> Option<Failure> {
+        let message = match (&self.embedding, &self.error) {
+            (Some(_), None) => return None,
+            (_, Some(e)) => e.clone(),
+            (None, None) => "Unknown embedding error".to_string(),
+        };
+        let lower = message.to_lowercase();
+        let kind = if self.error.is_none() {
+            FailureKind::Failed
+        } else if UNAVAILABLE_ERRORS.iter().any(|e| lower.contains(e)) {
+            FailureKind::Unavailable
+        } else if TIMEOUT_ERRORS.iter().any(|e| lower.contains(e)) {
+            FailureKind::Timeout
+        } else {
+            FailureKind::Rejected
+        };
+        Some(Failure { message, kind })
+    }
+
+    /// Преобразование ответа в вектор или ошибку Yandex из поля `error`
+    pub fn into_result(self) -> Result<Vec<f32>, ServiceError> {
+        match self.failure() {
+            Some(failure) => Err(failure.into_error("yandex")),
+            None => Ok(self.embedding.unwrap_or_default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::model::UpstreamErrorKind;
+
+    fn error_kind(error: &str) -> Option<UpstreamErrorKind> {
+        let response = Response {
+            embedding: None,
+            error: Some(error.to_string()),
+        };
+        match response.into_result() {
+            Err(ServiceError::Upstream { kind, .. }) => Some(kind),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn quota_errors_are_unavailable() {
+        #[rustfmt::skip]
+        let cases = [
+            ("Quota limit ai.embedding.requests.rate exceeded", UpstreamErrorKind::Unavailable),
+            ("RESOURCE_EXHAUSTED: too many requests", UpstreamErrorKind::Unavailable),
+            ("DEADLINE_EXCEEDED", UpstreamErrorKind::Timeout),
+            ("text is too long", UpstreamErrorKind::Failed),
+        ];
+        for (error, kind) in cases {
+            assert_eq!(error_kind(error), Some(kind), "{error}");
+        }
+        let response = Response {
+            embedding: None,
+            error: Some("Quota exceeded".to_string()),
+        };
+        assert!(response.failure().is_some_and(|f| f.retryable()));
+    }
+}
@@ -1,37 +1,101 @@
 use crate::embedding::interface::Embedding;
-use crate::embedding::model::{Request, Response};
+use crate::embedding::model::{Embeddings, Request, Response};
+use crate::error::model::ServiceError;
+use crate::resilience::model::Failure;
+use crate::resilience::upstream::Upstream;
 use crate::utils::funcs::keep_russian_and_dot;
+use futures::StreamExt;
+use std::collections::HashSet;
 
+#[derive(Clone)]
 pub struct YandexEmbedding {
     model: String,
     token: String,
     url: String,
+    dim: usize,
+    /// Сколько запросов может выполняться одновременно в `get_embeddings()`
+    max_concurrency: usize,
+    /// Ограничитель, повторы и предохранитель для запросов к Yandex
+    upstream: Upstream,
 }
 
 impl YandexEmbedding {
-    pub fn new(model: String, token: String, url: String) -> Self {
-        YandexEmbedding { model, token, url }
+    /// `dim` – размерность векторов модели `model`
+    pub fn new(
+        model: String,
+        token: String,
+        url: String,
+        dim: usize,
+        max_concurrency: usize,
+        upstream: Upstream,
+    ) -> Self {
+        YandexEmbedding {
+            model,
+            token,
+            url,
+            dim,
+            max_concurrency,
+            upstream,
+        }
     }
 }
 
 impl Embedding for YandexEmbedding {
-    /// Получение векторного представления текста в виде 256-мерного массива f32
-    async fn get_embedding(&self, text: &str) -> Result<Response, reqwest::Error> {
+    /// Получение векторного представления текста в виде массива f32 размерности `dim`
+    async fn get_embedding(&self, text: &str) -> Result<Response, ServiceError> {
         let sub_text = keep_russian_and_dot(text).to_lowercase();
         let request = Request {
             model_uri: self.model.clone(),
             text: sub_text,
         };
 
-        let client = reqwest::Client::new();
-        let resp = client
-            .post(&self.url)
-            .header("Authorization", format!("Api-Key {}", self.token))
-            .json(&request)
-            .send()
-            .await?;
+        self.upstream
+            .call(|| async {
+                let client = self.upstream.client();
+                let resp = client
+                    .post(&self.url)
+                    .header("Authorization", format!("Api-Key {}", self.token))
+                    .json(&request)
+                    .send()
+                    .await?;
 
-        let response: Response = resp.json().await?;
-        Ok(response)
+                let status = resp.status();
+                if !status.is_success() {
+                    let body = resp.text().await.unwrap_or_default();
+                    return Err(Failure::from_status(status, format!("{status}: {body}")));
+                }
+                let response: Response = resp.json().await?;
+                match response.failure() {
+                    Some(failure) => Err(failure),
+                    None => Ok(response),
+                }
+            })
+            .await
+    }
+
+    /// API Yandex принимает один текст за запрос, поэтому уникальные тексты
+    /// запрашиваются параллельно, но не больше `max_concurrency` одновременно.
+    /// Частоту запросов ограничивает `upstream`
+    async fn get_embeddings(&self, texts: &[String]) -> Embeddings {
+        let unique: HashSet<&String> = texts.iter().collect();
+        futures::stream::iter(unique)
+            .map(|text| async move {
+                let embedding = self
+                    .get_embedding(text)
+                    .await
+                    .and_then(Response::into_result);
+                (text.clone(), embedding)
+            })
+            .buffer_unordered(self.max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    fn dimension(&self) -> usize {
+        self.dim
+    }
+
+    fn model(&self) -> String {
+        self.model.clone()
     }
 }
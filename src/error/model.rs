@@ -0,0 +1,97 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+
+/// Ошибка проверки и API с HTTP-кодом и машиночитаемым `code`
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum ServiceError {
+    /// Неверный запрос: пустой текст, имя без русских букв, нечитаемый файл – 400
+    #[error("{0}")]
+    InvalidInput(String),
+    /// Нет записи реестра или статьи на сайте – 404
+    #[error("{0}")]
+    NotFound(String),
+    /// Ошибка внешнего сервиса: Yandex, NER или сайта
+    #[error("{upstream}: {message}")]
+    Upstream {
+        upstream: String,
+        kind: UpstreamErrorKind,
+        message: String,
+    },
+    /// Ошибка самого сервиса: база данных, индекс – 500
+    #[error("{0}")]
+    Internal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpstreamErrorKind {
+    /// Сервис ответил ошибкой или некорректным ответом – 502
+    Failed,
+    /// Сервис недоступен, превышена квота или разомкнут предохранитель – 503
+    Unavailable,
+    /// Сервис не ответил вовремя – 504
+    Timeout,
+}
+
+/// Тело ответа с ошибкой
+#[derive(Serialize)]
+pub struct ErrorS {
+    pub error: String,
+    /// Машиночитаемый код ошибки, например `upstream_timeout`
+    pub code: String,
+    /// Внешний сервис, из-за которого произошла ошибка: `yandex`, `ner` или `rv`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+}
+
+impl ServiceError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServiceError::InvalidInput(_) => "invalid_input",
+            ServiceError::NotFound(_) => "not_found",
+            ServiceError::Upstream { kind, .. } => match kind {
+                UpstreamErrorKind::Failed => "upstream_error",
+                UpstreamErrorKind::Unavailable => "upstream_unavailable",
+                UpstreamErrorKind::Timeout => "upstream_timeout",
+            },
+            ServiceError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl From<anyhow::Error> for ServiceError {
+    /// Типизированная ошибка сохраняется, остальные считаются внутренними
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<ServiceError>() {
+            Ok(e) => e,
+            Err(e) => ServiceError::Internal(format!("{e}")),
+        }
+    }
+}
+
+impl ResponseError for ServiceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ServiceError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServiceError::Upstream { kind, .. } => match kind {
+                UpstreamErrorKind::Failed => StatusCode::BAD_GATEWAY,
+                UpstreamErrorKind::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+                UpstreamErrorKind::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            },
+            ServiceError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let upstream = match self {
+            ServiceError::Upstream { upstream, .. } => Some(upstream.clone()),
+            _ => None,
+        };
+        HttpResponse::build(self.status_code()).json(ErrorS {
+            error: format!("{self}"),
+            code: self.code().to_string(),
+            upstream,
+        })
+    }
+}
@@ -0,0 +1,394 @@
+use crate::db::interface::DB;
+use crate::db::model::{Alias, Record, RecordCategory};
+use crate::embedding::interface::Embedding;
+use crate::error::model::ServiceError;
+use crate::import::model::{ImportReport, ImportedRecord};
+use crate::utils::funcs::{extract_aliases, keep_russian_and_dot};
+use std::collections::HashMap;
+
+/// Получение embedding имени записи с той же нормализацией, что и при проверке текста
+pub async fn embed_name<E: Embedding>(
+    vectorizer: &E,
+    name: &str,
+) -> Result<Vec<f32>, ServiceError> {
+    let normalized = keep_russian_and_dot(name);
+    if normalized.is_empty() {
+        return Err(ServiceError::InvalidInput(
+            "Имя не содержит русских букв".to_string(),
+        ));
+    }
+
+    let embedding = vectorizer.get_embedding(&normalized).await?.into_result()?;
+
+    if embedding.len() != vectorizer.dimension() {
+        return Err(ServiceError::Internal(format!(
+            "Embedding len {} != dim {}",
+            embedding.len(),
+            vectorizer.dimension()
+        )));
+    }
+    Ok(embedding)
+}
+
+/// Embedding имени записи или `None`, если в имени нет русских букв: такие
+/// записи хранятся без embedding, как и псевдонимы латиницей
+async fn embed_record_name<E: Embedding>(
+    vectorizer: &E,
+    name: &str,
+) -> Result<Option<Vec<f32>>, ServiceError> {
+    if !has_russian(name) {
+        return Ok(None);
+    }
+    embed_name(vectorizer, name).await.map(Some)
+}
+
+/// Для имени считается embedding: в нем есть русские буквы
+fn has_russian(name: &str) -> bool {
+    !keep_russian_and_dot(name).is_empty()
+}
+
+/// Псевдонимы из кавычек в имени с embedding, см. `embed_missing_aliases()`
+pub async fn embed_aliases<E: Embedding>(
+    vectorizer: &E,
+    name: &str,
+) -> Result<Vec<Alias>, ServiceError> {
+    embed_alias_names(vectorizer, extract_aliases(name)).await
+}
+
+/// Псевдонимы записи `name`: заданные явно или, если их нет, из кавычек в имени.
+/// Пустые строки, повторы и само имя отбрасываются
+pub fn alias_names(name: &str, explicit: Option<&[String]>) -> Vec<String> {
+    let Some(explicit) = explicit else {
+        return extract_aliases(name);
+    };
+    let mut aliases: Vec<String> = Vec::new();
+    for alias in explicit.iter().map(|a| a.trim()) {
+        if !alias.is_empty() && alias != name.trim() && !aliases.iter().any(|a| a == alias) {
+            aliases.push(alias.to_string());
+        }
+    }
+    aliases
+}
+
+/// Псевдонимы с именами `names` и embedding, см. `embed_missing_aliases()`
+pub async fn embed_alias_names<E: Embedding>(
+    vectorizer: &E,
+    names: Vec<String>,
+) -> Result<Vec<Alias>, ServiceError> {
+    let mut aliases: Vec<Alias> = names
+        .into_iter()
+        .map(|name| Alias {
+            name,
+            embedding: None,
+        })
+        .collect();
+    embed_missing_aliases(vectorizer, &mut aliases).await?;
+    Ok(aliases)
+}
+
+/// Embedding псевдонимов, у которых его еще нет. Псевдонимы без русских букв
+/// остаются без embedding. Ошибка модели возвращается, а не проглатывается:
+/// иначе псевдоним сохранился бы без embedding и больше не пересчитывался
+pub async fn embed_missing_aliases<E: Embedding>(
+    vectorizer: &E,
+    aliases: &mut [Alias],
+) -> Result<(), ServiceError> {
+    for alias in aliases.iter_mut().filter(|a| a.embedding.is_none()) {
+        alias.embedding = embed_record_name(vectorizer, &alias.name).await?;
+    }
+    Ok(())
+}
+
+/// У записи нет псевдонимов из кавычек в имени или у псевдонима с русскими
+/// буквами нет embedding
+fn aliases_incomplete(record: &Record) -> bool {
+    if record.aliases.is_empty() {
+        return !extract_aliases(&record.name).is_empty();
+    }
+    record
+        .aliases
+        .iter()
+        .any(|a| a.embedding.is_none() && has_russian(&a.name))
+}
+
+/// Псевдонимы записи с досчитанными embedding. Если псевдонимов еще нет,
+/// они извлекаются из имени
+async fn complete_aliases<E: Embedding>(
+    vectorizer: &E,
+    record: &Record,
+) -> Result<Vec<Alias>, ServiceError> {
+    if record.aliases.is_empty() {
+        return embed_aliases(vectorizer, &record.name).await;
+    }
+    let mut aliases = record.aliases.clone();
+    embed_missing_aliases(vectorizer, &mut aliases).await?;
+    Ok(aliases)
+}
+
+/// Построение записи реестра с embedding имени и всех псевдонимов, см. `alias_names()`.
+/// Тип записи проверяется до запроса embedding
+pub async fn build_record<E: Embedding>(
+    vectorizer: &E,
+    imported: ImportedRecord,
+) -> Result<Record, ServiceError> {
+    imported.record_type.validate()?;
+    let embedding = embed_record_name(vectorizer, &imported.name).await?;
+    let names = alias_names(&imported.name, imported.aliases.as_deref());
+    let aliases = embed_alias_names(vectorizer, names).await?;
+    Ok(Record {
+        id: 0,
+        name: imported.name,
+        record_type: imported.record_type,
+        embedding,
+        is_removed: imported.is_removed,
+        aliases,
+        created_at: None,
+        updated_at: None,
+    })
+}
+
+/// Записи выгрузки, подготовленные к сохранению: с embedding и итогами сравнения с базой
+pub struct PreparedImport {
+    /// Новые и измененные записи в порядке выгрузки
+    records: Vec<Record>,
+    report: ImportReport,
+}
+
+/// Загрузка записей выгрузки в базу данных, см. `prepare_import()`
+pub async fn import_records<D: DB, E: Embedding>(
+    db: &D,
+    vectorizer: &E,
+    records: Vec<ImportedRecord>,
+) -> Result<ImportReport, anyhow::Error> {
+    let prepared = prepare_import(db.get_all()?, vectorizer, records).await;
+    apply_import(db, prepared)
+}
+
+/// Сравнение выгрузки с записями базы `existing`.
+///
+/// Embedding запрашивается только для имен, которых еще нет в базе,
+/// у существующих записей обновляется флаг `is_removed` и досчитываются
+/// псевдонимы, если их еще нет. База данных при этом не нужна, поэтому
+/// она не блокируется на время запросов embedding
+pub async fn prepare_import<E: Embedding>(
+    existing: Vec<Record>,
+    vectorizer: &E,
+    records: Vec<ImportedRecord>,
+) -> PreparedImport {
+    let mut existing: HashMap<(String, RecordCategory), Record> = existing
+        .into_iter()
+        .map(|r| ((r.name.clone(), r.record_type.clone()), r))
+        .collect();
+
+    let mut prepared = PreparedImport {
+        records: Vec::new(),
+        report: ImportReport::default(),
+    };
+    let report = &mut prepared.report;
+
+    for imported in records {
+        let key = (imported.name.clone(), imported.record_type.clone());
+
+        if let Some(record) = existing.get_mut(&key) {
+            let flag_changed = record.is_removed != imported.is_removed;
+            let incomplete = aliases_incomplete(record);
+            if !flag_changed && !incomplete {
+                report.unchanged += 1;
+                continue;
+            }
+            record.is_removed = imported.is_removed;
+            let mut aliases_changed = false;
+            if incomplete {
+                match complete_aliases(vectorizer, record).await {
+                    Ok(aliases) => {
+                        record.aliases = aliases;
+                        aliases_changed = true;
+                    }
+                    Err(e) => report.errors.push(format!("{}: {e}", record.name)),
+                }
+            }
+            if flag_changed || aliases_changed {
+                prepared.records.push(record.clone());
+                report.updated += 1;
+            }
+            continue;
+        }
+
+        let name = imported.name.clone();
+        let record = match build_record(vectorizer, imported).await {
+            Ok(r) => r,
+            Err(e) => {
+                report.errors.push(format!("{name}: {e}"));
+                continue;
+            }
+        };
+        prepared.records.push(record.clone());
+        existing.insert(key, record);
+        report.inserted += 1;
+    }
+
+    prepared
+}
+
+/// Сохранение подготовленных записей. Записи ищутся в базе по имени и типу,
+/// поэтому запись, добавленная после `prepare_import()`, не дублируется
+pub fn apply_import<D: DB>(
+    db: &D,
+    prepared: PreparedImport,
+) -> Result<ImportReport, anyhow::Error> {
+    db.upsert_all(&prepared.records)?;
+    Ok(prepared.report)
+}
+
+/// Пересчет embedding всех записей и их псевдонимов текущей моделью – после смены модели.
+///
+/// Записи, для которых не удалось получить embedding, остаются со старым вектором
+pub async fn reembed_records<D: DB, E: Embedding>(
+    db: &D,
+    vectorizer: &E,
+) -> Result<ImportReport, anyhow::Error> {
+    let mut report = ImportReport::default();
+
+    for mut record in db.get_all()? {
+        record.embedding = match embed_record_name(vectorizer, &record.name).await {
+            Ok(e) => e,
+            Err(e) => {
+                report.errors.push(format!("{}: {e}", record.name));
+                continue;
+            }
+        };
+        // Названия псевдонимов сохраняются, пересчитываются только их embedding
+        let mut aliases = record.aliases.clone();
+        aliases.iter_mut().for_each(|a| a.embedding = None);
+        if let Err(e) = embed_missing_aliases(vectorizer, &mut aliases).await {
+            report.errors.push(format!("{}: {e}", record.name));
+            continue;
+        }
+        record.aliases = aliases;
+        db.update(&record)?;
+        report.updated += 1;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::local::LocalEmbedding;
+    use crate::embedding::model::Response;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Локальная векторизация, которая отказывает на тексте `сбой`,
+    /// пока не снят флаг `failing`
+    struct Flaky {
+        inner: LocalEmbedding,
+        failing: AtomicBool,
+    }
+
+    impl Embedding for Flaky {
+        async fn get_embedding(&self, text: &str) -> Result<Response, ServiceError> {
+            if self.failing.load(Ordering::Relaxed) && text == "сбой" {
+                return Err(ServiceError::Internal("модель недоступна".to_string()));
+            }
+            self.inner.get_embedding(text).await
+        }
+
+        fn dimension(&self) -> usize {
+            self.inner.dimension()
+        }
+
+        fn model(&self) -> String {
+            self.inner.model()
+        }
+    }
+
+    fn imported(name: &str) -> ImportedRecord {
+        ImportedRecord {
+            name: name.to_string(),
+            record_type: RecordCategory::ForeignAgent,
+            is_removed: false,
+            aliases: None,
+        }
+    }
+
+    #[test]
+    fn failed_alias_is_reported_and_retried() {
+        let vectorizer = Flaky {
+            inner: LocalEmbedding::new(16),
+            failing: AtomicBool::new(true),
+        };
+        let name = r#"Иванов Иван "Ваня, сбой, Dry Ice""#;
+
+        let prepared = futures::executor::block_on(prepare_import(
+            Vec::new(),
+            &vectorizer,
+            vec![imported(name)],
+        ));
+        assert_eq!(prepared.report.inserted, 0);
+        assert_eq!(prepared.report.errors.len(), 1);
+
+        // Запись сохранена раньше с псевдонимом без embedding
+        let mut existing = futures::executor::block_on(build_record(
+            &Flaky {
+                inner: LocalEmbedding::new(16),
+                failing: AtomicBool::new(false),
+            },
+            imported(name),
+        ))
+        .unwrap();
+        existing.id = 1;
+        existing.aliases[1].embedding = None;
+        assert!(existing.aliases[2].embedding.is_none());
+
+        let prepared = futures::executor::block_on(prepare_import(
+            vec![existing.clone()],
+            &vectorizer,
+            vec![imported(name)],
+        ));
+        assert_eq!(
+            (prepared.report.updated, prepared.report.errors.len()),
+            (0, 1)
+        );
+
+        vectorizer.failing.store(false, Ordering::Relaxed);
+        let prepared = futures::executor::block_on(prepare_import(
+            vec![existing],
+            &vectorizer,
+            vec![imported(name)],
+        ));
+        assert_eq!(prepared.report.updated, 1);
+        let aliases = &prepared.records[0].aliases;
+        assert!(aliases[1].embedding.is_some());
+        // Псевдоним латиницей остается без embedding
+        assert!(aliases[2].embedding.is_none());
+    }
+
+    #[test]
+    fn explicit_aliases_replace_quoted() {
+        let name = r#"Иванов Иван "Ваня Рок""#;
+        let explicit = |aliases: &[&str]| aliases.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(alias_names(name, None), ["Ваня Рок"]);
+        assert_eq!(
+            alias_names(name, Some(&explicit(&[" Ваня ", "", "Ваня", "Ivan", name]))),
+            ["Ваня", "Ivan"]
+        );
+        assert!(alias_names(name, Some(&[])).is_empty());
+
+        let record = futures::executor::block_on(build_record(
+            &LocalEmbedding::new(16),
+            ImportedRecord {
+                aliases: Some(explicit(&["Ваня", "Ivan"])),
+                ..imported(name)
+            },
+        ))
+        .unwrap();
+        let aliases: Vec<(&str, bool)> = record
+            .aliases
+            .iter()
+            .map(|a| (a.name.as_str(), a.embedding.is_some()))
+            .collect();
+        assert_eq!(aliases, [("Ваня", true), ("Ivan", false)]);
+    }
+}
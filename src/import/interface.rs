@@ -0,0 +1,7 @@
+use crate::import::model::ImportedRecord;
+use std::path::Path;
+
+pub trait RegistryReader {
+    /// Чтение записей реестра из файла выгрузки
+    fn read_records(&self, path: &Path) -> Result<Vec<ImportedRecord>, anyhow::Error>;
+}
@@ -0,0 +1,114 @@
+use crate::db::model::{RecordCategory, Severity};
+use serde::Serialize;
+
+/// Вид реестра: тип записей в базе, колонки выгрузки и пороги сравнения имен
+pub struct RegistryKind {
+    pub category: RecordCategory,
+    pub title: &'static str,
+    /// Варианты заголовка колонки с наименованием, по порядку предпочтения
+    pub name_headers: &'static [&'static str],
+    /// Варианты заголовка колонки с датой исключения. Пусто, если в выгрузке ее нет
+    pub removal_headers: &'static [&'static str],
+    /// Минимальное косинусное сходство
+    pub max_treshold: f32,
+    /// Максимальное итоговое расстояние
+    pub max_dis: usize,
+    /// Пометка, которая вставляется после упоминания: `{label}` – правовой статус
+    /// в нижнем регистре, `{name}` – имя записи
+    pub label_template: &'static str,
+}
+
+/// Известные виды реестров. Первый используется для типов `RecordCategory::Other`
+pub const REGISTRY_KINDS: [RegistryKind; 4] = [
+    RegistryKind {
+        category: RecordCategory::ForeignAgent,
+        title: "Реестр иностранных агентов",
+        name_headers: &["Полное наименование"],
+        removal_headers: &["об исключении"],
+        max_treshold: 0.61,
+        max_dis: 7,
+        label_template: " ({label})",
+    },
+    // В названиях организаций меньше падежных форм, чем в ФИО, поэтому пороги строже
+    RegistryKind {
+        category: RecordCategory::Undesirable,
+        title: "Перечень нежелательных организаций",
+        name_headers: &["Полное наименование", "Наименование"],
+        removal_headers: &["об исключении", "исключени"],
+        max_treshold: 0.65,
+        max_dis: 5,
+        label_template: " (деятельность организации признана нежелательной на территории РФ)",
+    },
+    RegistryKind {
+        category: RecordCategory::Extremist,
+        title: "Перечень экстремистских организаций",
+        name_headers: &["Полное наименование", "Наименование"],
+        removal_headers: &[],
+        max_treshold: 0.65,
+        max_dis: 5,
+        label_template: " (организация признана экстремистской и запрещена в РФ)",
+    },
+    RegistryKind {
+        category: RecordCategory::Terrorist,
+        title: "Перечень террористических организаций",
+        name_headers: &["Полное наименование", "Наименование"],
+        removal_headers: &[],
+        max_treshold: 0.65,
+        max_dis: 5,
+        label_template: " (организация признана террористической и запрещена в РФ)",
+    },
+];
+
+/// Вид реестра по типу записи, для своего типа – реестр иностранных агентов
+pub fn registry_kind(category: &RecordCategory) -> &'static RegistryKind {
+    REGISTRY_KINDS
+        .iter()
+        .find(|k| &k.category == category)
+        .unwrap_or(&REGISTRY_KINDS[0])
+}
+
+/// Шаблон пометки по умолчанию. У своих видов реестра пометки нет
+pub fn label_template(category: &RecordCategory) -> Option<&'static str> {
+    REGISTRY_KINDS
+        .iter()
+        .find(|k| &k.category == category)
+        .map(|k| k.label_template)
+}
+
+/// Самое мягкое сходство среди всех видов – порог первичного отбора из индекса
+pub fn min_treshold() -> f32 {
+    REGISTRY_KINDS
+        .iter()
+        .map(|k| k.max_treshold)
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Вид реестра для `GET /categories`
+#[derive(Serialize)]
+pub struct CategoryInfo {
+    pub code: RecordCategory,
+    pub title: &'static str,
+    pub legal_label: &'static str,
+    pub severity: Severity,
+    pub max_treshold: f32,
+    pub max_dis: usize,
+    pub label_template: &'static str,
+}
+
+/// Все известные виды реестров, от самых серьезных
+pub fn categories() -> Vec<CategoryInfo> {
+    let mut categories: Vec<CategoryInfo> = REGISTRY_KINDS
+        .iter()
+        .map(|k| CategoryInfo {
+            code: k.category.clone(),
+            title: k.title,
+            legal_label: k.category.legal_label(),
+            severity: k.category.severity(),
+            max_treshold: k.max_treshold,
+            max_dis: k.max_dis,
+            label_template: k.label_template,
+        })
+        .collect();
+    categories.sort_by_key(|c| std::cmp::Reverse(c.severity));
+    categories
+}
@@ -0,0 +1,5 @@
+pub mod importer;
+pub mod interface;
+pub mod kinds;
+pub mod model;
+pub mod xlsx;
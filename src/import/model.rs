@@ -0,0 +1,28 @@
+use crate::db::model::RecordCategory;
+use serde::{Deserialize, Serialize};
+
+/// Запись реестра в том виде, в котором она пришла из выгрузки или `/add` (без embedding)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportedRecord {
+    pub name: String,
+    pub record_type: RecordCategory,
+    #[serde(default)]
+    pub is_removed: bool,
+    /// Псевдонимы записи. Если не заданы, извлекаются из кавычек в имени
+    pub aliases: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportRequest {
+    pub path: String,
+    pub record_type: Option<RecordCategory>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
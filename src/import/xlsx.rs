@@ -0,0 +1,181 @@
+use crate::db::model::RecordCategory;
+use crate::import::interface::RegistryReader;
+use crate::import::kinds::registry_kind;
+use crate::import::model::ImportedRecord;
+use calamine::{Data, Range, Reader as _, Xlsx};
+use regex::Regex;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use std::sync::LazyLock;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const NUMBER_HEADER: &str = "№ п/п";
+const SHARED_STRINGS: &str = "xl/sharedStrings.xml";
+
+/// Пустая общая строка, возможно с префиксом пространства имен: `<si/>`, `<x:si />`
+static EMPTY_SHARED_STRING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(?<p>\w+:)?si\s*/>").unwrap());
+
+/// Чтение выгрузок реестров Минюста (`assets/export.xlsx`, `assets/nezh.xlsx`).
+/// Колонки ищутся по заголовкам вида реестра `record_type`
+pub struct MinjustXlsx {
+    record_type: RecordCategory,
+}
+
+impl MinjustXlsx {
+    pub fn new(record_type: RecordCategory) -> Self {
+        MinjustXlsx { record_type }
+    }
+}
+
+impl RegistryReader for MinjustXlsx {
+    fn read_records(&self, path: &Path) -> Result<Vec<ImportedRecord>, anyhow::Error> {
+        let range = read_first_sheet(path)?;
+
+        let mut rows = range.rows();
+
+        // Пропускаем заголовок реестра и дату выгрузки до строки с названиями колонок
+        let header = rows
+            .by_ref()
+            .find(|row| row.iter().any(|c| cell_text(c) == NUMBER_HEADER))
+            .ok_or_else(|| anyhow::anyhow!("Не найдена строка заголовков в {}", path.display()))?;
+
+        let kind = registry_kind(&self.record_type);
+        let name_col = find_column(header, kind.name_headers).ok_or_else(|| {
+            anyhow::anyhow!("Не найдена колонка с наименованием в {}", path.display())
+        })?;
+        let removal_col = if kind.removal_headers.is_empty() {
+            None
+        } else {
+            Some(find_column(header, kind.removal_headers).ok_or_else(|| {
+                anyhow::anyhow!("Не найдена колонка с датой исключения в {}", path.display())
+            })?)
+        };
+
+        let mut records = Vec::new();
+        for row in rows {
+            let name = row.get(name_col).map(cell_text).unwrap_or_default();
+            if name.is_empty() {
+                continue;
+            }
+            let removal_date = removal_col
+                .and_then(|col| row.get(col))
+                .map(cell_text)
+                .unwrap_or_default();
+            records.push(ImportedRecord {
+                name,
+                record_type: self.record_type.clone(),
+                is_removed: !removal_date.is_empty(),
+                aliases: None,
+            });
+        }
+
+        Ok(records)
+    }
+}
+
+/// Чтение первого листа книги
+fn read_first_sheet(path: &Path) -> Result<Range<Data>, anyhow::Error> {
+    let bytes = fs::read(path)?;
+    let bytes = fill_empty_shared_strings(&bytes)?.unwrap_or(bytes);
+    let mut workbook = Xlsx::new(Cursor::new(bytes))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| anyhow::anyhow!("В файле {} нет листов", path.display()))??;
+    Ok(range)
+}
+
+/// calamine пропускает пустые общие строки `<si/>` (они есть в `assets/nezh.xlsx`),
+/// из-за этого номера строк сдвигаются и чтение падает с паникой. Такие строки
+/// заменяются на `<si><t></t></si>` в копии книги, остальные файлы архива
+/// копируются как есть. `None`, если заменять нечего
+fn fill_empty_shared_strings(bytes: &[u8]) -> Result<Option<Vec<u8>>, anyhow::Error> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    let mut shared_strings = String::new();
+    match archive.by_name(SHARED_STRINGS) {
+        Ok(mut file) => file.read_to_string(&mut shared_strings)?,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if !EMPTY_SHARED_STRING.is_match(&shared_strings) {
+        return Ok(None);
+    }
+    let shared_strings =
+        EMPTY_SHARED_STRING.replace_all(&shared_strings, "<${p}si><${p}t></${p}t></${p}si>");
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        if file.name() == SHARED_STRINGS {
+            let options =
+                SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+            writer.start_file(SHARED_STRINGS, options)?;
+            writer.write_all(shared_strings.as_bytes())?;
+        } else {
+            writer.raw_copy_file(file)?;
+        }
+    }
+    Ok(Some(writer.finish()?.into_inner()))
+}
+
+/// Колонка с первым из вариантов заголовка, который есть в строке заголовков
+fn find_column(header: &[Data], patterns: &[&str]) -> Option<usize> {
+    patterns
+        .iter()
+        .find_map(|p| header.iter().position(|c| cell_text(c).contains(p)))
+}
+
+/// Текст ячейки без переносов строк и повторяющихся пробелов
+fn cell_text(cell: &Data) -> String {
+    cell.to_string()
+        .replace('\u{a0}', " ")
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_shared_strings_are_filled() {
+        let bytes = fs::read("assets/nezh.xlsx").unwrap();
+        let patched = fill_empty_shared_strings(&bytes).unwrap().unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(patched.as_slice())).unwrap();
+        assert_eq!(
+            archive.len(),
+            ZipArchive::new(Cursor::new(&bytes)).unwrap().len()
+        );
+        let mut xml = String::new();
+        let mut file = archive.by_name(SHARED_STRINGS).unwrap();
+        file.read_to_string(&mut xml).unwrap();
+        assert!(!EMPTY_SHARED_STRING.is_match(&xml));
+        assert!(xml.contains("<si><t></t></si>"));
+    }
+
+    #[test]
+    fn both_exports_are_read() {
+        // Путь, вид реестра, записей всего и исключенных
+        let exports = [
+            ("assets/nezh.xlsx", "nezh", 252, 0),
+            ("assets/export.xlsx", "ino", 1050, 232),
+        ];
+        for (path, record_type, total, removed) in exports {
+            let records = MinjustXlsx::new(RecordCategory::from(record_type.to_string()))
+                .read_records(Path::new(path))
+                .unwrap();
+            assert_eq!(records.len(), total, "{path}");
+            assert_eq!(
+                records.iter().filter(|r| r.is_removed).count(),
+                removed,
+                "{path}"
+            );
+            assert!(records.iter().all(|r| !r.name.is_empty()));
+        }
+    }
+}
@@ -1,33 +1,63 @@
-use crate::ino_api::server_api::{Checker, ErrorS};
-use actix_web::{HttpResponse, web};
+use crate::ino_api::server_api::{Checker, RecordAction, RecordsQuery};
+use actix_web::{HttpResponse, ResponseError, web};
 use serde::Deserialize;
 
-use crate::db::model::Record;
 use crate::db::sqlite::Database;
-use crate::embedding::vectorize::YandexEmbedding;
+use crate::embedding::backend::EmbeddingBackend;
+use crate::embedding::cache::CachedEmbedding;
+use crate::import::kinds::categories;
+use crate::import::model::{ImportRequest, ImportedRecord};
+use crate::ino_checker::fixer::LabelTemplates;
 use crate::ino_checker::new_checker::WarningNamesChecker;
 use crate::ino_checker::new_name_checker::NameChecker;
-use crate::ner::entities::PythonEntities;
+use crate::ner::backend::EntitiesBackend;
+use crate::ner::model::Entity;
+use crate::resilience::model::{CircuitState, HealthReport, UpstreamHealth};
+use crate::resilience::upstream::Upstream;
 
-pub type ApiChecker =
-    Checker<WarningNamesChecker<YandexEmbedding, NameChecker, PythonEntities>, Database>;
+pub type ApiChecker = Checker<
+    WarningNamesChecker<CachedEmbedding<EmbeddingBackend>, NameChecker, EntitiesBackend>,
+    Database,
+    CachedEmbedding<EmbeddingBackend>,
+>;
 
 #[derive(Deserialize)]
 pub struct TextRequest {
     pub text: String,
 }
 
+/// Имя из текста и запись реестра для разбора сравнения
+#[derive(Deserialize)]
+pub struct ExplainRequest {
+    pub entity: String,
+    pub record_id: i64,
+    /// Нормальная форма имени, по умолчанию совпадает с `entity`
+    pub norm_name: Option<String>,
+    /// `PER` или `ORG`, по умолчанию `PER`
+    pub entity_type: Option<String>,
+}
+
+/// Текст или id статьи для расстановки пометок
+#[derive(Deserialize)]
+pub struct FixRequest {
+    pub text: Option<String>,
+    pub id: Option<String>,
+    /// Шаблоны пометок редакции по кодам видов реестра
+    #[serde(default)]
+    pub templates: LabelTemplates,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct Records {
-    pub records: Vec<Record>,
+    pub records: Vec<ImportedRecord>,
 }
 
-/// Добавление новых запрещенных имен в память - POST
+/// Добавление новых запрещенных имен в базу данных и память - POST
 pub async fn add_new_names(
     checker: web::Data<ApiChecker>,
     req: web::Json<Records>,
 ) -> HttpResponse {
-    checker.add_warning_names(req.records.clone()).await
+    checker.add_warning_names(req.into_inner().records).await
 }
 
 /// Проверка наличия запрещенных имен по тексту - POST
@@ -40,6 +70,11 @@ pub async fn check_by_text(
         .await
 }
 
+/// Виды реестров: коды `record_type`, правовой статус, серьезность и пороги - GET
+pub async fn list_categories() -> HttpResponse {
+    HttpResponse::Ok().json(categories())
+}
+
 /// Проверка наличия запрещенных имен по id - GET
 pub async fn check_by_id_handler(
     checker: web::Data<ApiChecker>,
@@ -50,12 +85,119 @@ pub async fn check_by_id_handler(
         .await
 }
 
+/// Проверка пометок у упоминаний по тексту - POST
+pub async fn compliance_by_text(
+    checker: web::Data<ApiChecker>,
+    req: web::Json<TextRequest>,
+) -> HttpResponse {
+    checker.compliance_by_text(req.into_inner().text).await
+}
+
+/// Проверка пометок у упоминаний по id - GET
+pub async fn compliance_by_id(
+    checker: web::Data<ApiChecker>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    checker.compliance_by_id(path.into_inner()).await
+}
+
+/// Пометки и исправленный текст по тексту или id - POST
+pub async fn fix(checker: web::Data<ApiChecker>, req: web::Json<FixRequest>) -> HttpResponse {
+    let req = req.into_inner();
+    checker.fix(req.text, req.id, req.templates).await
+}
+
+/// Разбор сравнения имени с записью реестра - POST
+pub async fn explain(
+    checker: web::Data<ApiChecker>,
+    req: web::Json<ExplainRequest>,
+) -> HttpResponse {
+    let req = req.into_inner();
+    let entity = Entity {
+        norm_name: req.norm_name.unwrap_or_else(|| req.entity.clone()),
+        name: req.entity,
+        entity_type: req.entity_type.unwrap_or_else(|| "PER".to_string()),
+        context: String::new(),
+    };
+    checker.explain(entity, req.record_id).await
+}
+
 /// Обновление списка запрещенных имен в памяти после обновления БД - GET
 pub async fn update_inos(checker: web::Data<ApiChecker>) -> HttpResponse {
     match checker.update_warning_names().await {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(e) => HttpResponse::InternalServerError().json(ErrorS {
-            error: format!("{e}"),
-        }),
+        Ok(stats) => HttpResponse::Ok().json(stats),
+        Err(e) => e.error_response(),
     }
 }
+
+/// Импорт выгрузки реестра Минюста в базу данных - POST
+pub async fn import_registry(
+    checker: web::Data<ApiChecker>,
+    req: web::Json<ImportRequest>,
+) -> HttpResponse {
+    checker.import_registry(req.into_inner()).await
+}
+
+/// Список записей реестра с фильтрами и поиском - GET
+pub async fn list_records(
+    checker: web::Data<ApiChecker>,
+    query: web::Query<RecordsQuery>,
+) -> HttpResponse {
+    checker.list_records(query.into_inner()).await
+}
+
+/// Изменение записи реестра по id - PUT
+pub async fn update_record(
+    checker: web::Data<ApiChecker>,
+    path: web::Path<i64>,
+    req: web::Json<ImportedRecord>,
+) -> HttpResponse {
+    checker
+        .update_record(path.into_inner(), req.into_inner())
+        .await
+}
+
+/// Получение записи реестра по id - GET
+pub async fn get_record(checker: web::Data<ApiChecker>, path: web::Path<i64>) -> HttpResponse {
+    checker.get_record(path.into_inner()).await
+}
+
+/// Пометка записи как исключенной из реестра - POST
+pub async fn remove_record(checker: web::Data<ApiChecker>, path: web::Path<i64>) -> HttpResponse {
+    checker
+        .change_record(path.into_inner(), RecordAction::Remove)
+        .await
+}
+
+/// Возврат исключенной записи в реестр - POST
+pub async fn restore_record(checker: web::Data<ApiChecker>, path: web::Path<i64>) -> HttpResponse {
+    checker
+        .change_record(path.into_inner(), RecordAction::Restore)
+        .await
+}
+
+/// Полное удаление записи из базы данных - DELETE
+pub async fn delete_record(checker: web::Data<ApiChecker>, path: web::Path<i64>) -> HttpResponse {
+    checker
+        .change_record(path.into_inner(), RecordAction::Delete)
+        .await
+}
+
+/// Счетчики кеша embedding - GET
+pub async fn embedding_cache_stats(checker: web::Data<ApiChecker>) -> HttpResponse {
+    HttpResponse::Ok().json(checker.vectorizer().stats())
+}
+
+/// Состояние внешних сервисов: предохранители, ограничители и счетчики вызовов - GET
+pub async fn health(upstreams: web::Data<Vec<Upstream>>) -> HttpResponse {
+    let upstreams: Vec<UpstreamHealth> = upstreams.iter().map(Upstream::health).collect();
+    let status = if upstreams.iter().all(|u| u.state == CircuitState::Closed) {
+        "ok"
+    } else {
+        "degraded"
+    };
+    HttpResponse::Ok().json(HealthReport {
+        status: status.to_string(),
+        upstreams,
+    })
+}
@@ -1,109 +1,502 @@
 use crate::db::interface::DB;
-use std::time::Duration;
+use crate::db::model::{Record, RecordCategory, RecordFilter, RecordInfo};
+use crate::embedding::interface::Embedding;
+use crate::import::importer::{
+    alias_names, apply_import, build_record, embed_alias_names, prepare_import,
+};
+use crate::import::interface::RegistryReader;
+use crate::import::model::{ImportRequest, ImportedRecord};
+use crate::import::xlsx::MinjustXlsx;
 
+use crate::error::model::ServiceError;
+use crate::ino_checker::fixer::LabelTemplates;
 use crate::ino_checker::interface::BasicChecker;
-use actix_web::HttpResponse;
+use crate::ino_checker::model::UpdateStats;
+use crate::ner::model::Entity;
+use crate::utils::funcs::unordered_levenshtein;
+use actix_web::{HttpResponse, ResponseError};
 use anyhow::Error;
-use serde::Serialize;
-use tokio::time::sleep;
+use serde::{Deserialize, Serialize};
 
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::db::model::Record;
-
-pub struct Checker<T: BasicChecker, D: DB> {
+pub struct Checker<T: BasicChecker, D: DB, V: Embedding> {
     pub need_full_data: bool,
-    checker: Mutex<T>,
+    /// Checker сам защищает свое состояние, поэтому `/update` не блокирует проверки
+    checker: T,
     database: Arc<Mutex<D>>,
+    vectorizer: V,
+    /// Каталог, из которого `/import` читает выгрузки
+    import_dir: PathBuf,
+}
+
+#[derive(Serialize)]
+pub struct AddResult {
+    pub name: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Машиночитаемый код ошибки, как в ответах с ошибкой
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+impl AddResult {
+    fn added(name: String, id: i64) -> Self {
+        AddResult {
+            name,
+            success: true,
+            id: Some(id),
+            code: None,
+            error: None,
+        }
+    }
+
+    /// Запись сохранена в базу данных, но не попала в индекс в памяти
+    fn not_indexed(name: String, id: i64, e: String) -> Self {
+        AddResult {
+            id: Some(id),
+            ..AddResult::failed(name, ServiceError::Internal(e))
+        }
+    }
+
+    fn failed(name: String, e: ServiceError) -> Self {
+        AddResult {
+            name,
+            success: false,
+            id: None,
+            code: Some(e.code().to_string()),
+            error: Some(format!("{e}")),
+        }
+    }
 }
 
 #[derive(Serialize)]
-pub struct ErrorS {
-    pub error: String,
+pub struct AddResponse {
+    pub results: Vec<AddResult>,
+}
+
+#[derive(Deserialize)]
+pub struct RecordsQuery {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    pub record_type: Option<RecordCategory>,
+    pub is_removed: Option<bool>,
+    /// Поиск по имени и псевдонимам: подстрока или нечеткое совпадение
+    pub q: Option<String>,
+    /// Максимальное расстояние `unordered_levenshtein` для нечеткого поиска
+    pub max_distance: Option<usize>,
 }
 
-impl<T: BasicChecker, D: DB> Checker<T, D> {
-    pub fn new(need_full_data: bool, checker: T, database: Arc<Mutex<D>>) -> Result<Self, Error> {
+#[derive(Serialize)]
+pub struct RecordsPage {
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    pub records: Vec<RecordInfo>,
+}
+
+pub enum RecordAction {
+    Remove,
+    Restore,
+    Delete,
+}
+
+const DEFAULT_PER_PAGE: usize = 50;
+const MAX_PER_PAGE: usize = 500;
+const DEFAULT_SEARCH_DISTANCE: usize = 3;
+
+impl<T: BasicChecker, D: DB, V: Embedding> Checker<T, D, V> {
+    pub fn new(
+        need_full_data: bool,
+        checker: T,
+        database: Arc<Mutex<D>>,
+        vectorizer: V,
+        import_dir: PathBuf,
+    ) -> Result<Self, Error> {
         Ok(Checker {
             need_full_data,
-            checker: Mutex::new(checker),
+            checker,
             database,
+            vectorizer,
+            import_dir,
         })
     }
 
+    pub fn vectorizer(&self) -> &V {
+        &self.vectorizer
+    }
+
     /// Проверка наличия запрещенных имен по id текста на сайте
     pub async fn check_by_id(&self, id: String, need_full_data: bool) -> HttpResponse {
-        let mut i: u8 = 0;
-        loop {
-            match self
-                .checker
-                .lock()
-                .await
-                .get_inos(&id, need_full_data)
-                .await
-            {
-                Err(e) => {
-                    i += 1;
+        match self.checker.get_inos(&id, need_full_data).await {
+            Ok(inos) => HttpResponse::Ok().json(inos),
+            Err(e) => e.error_response(),
+        }
+    }
 
-                    if i >= 4 {
-                        let err = ErrorS {
-                            error: format!("{e}"),
-                        };
-                        return HttpResponse::InternalServerError().json(err);
-                    }
+    /// Проверка наличия запрещенных имен по тексту
+    pub async fn check_by_text(&self, text: String, need_full_data: bool) -> HttpResponse {
+        match self.checker.get_inos_from_text(&text, need_full_data).await {
+            Ok(inos) => HttpResponse::Ok().json(inos),
+            Err(e) => e.error_response(),
+        }
+    }
 
-                    eprintln!("{e}");
-                    sleep(Duration::from_secs(1)).await;
+    /// Проверка пометок у упоминаний записей реестра по тексту
+    pub async fn compliance_by_text(&self, text: String) -> HttpResponse {
+        match self.checker.check_compliance_from_text(&text).await {
+            Ok(report) => HttpResponse::Ok().json(report),
+            Err(e) => e.error_response(),
+        }
+    }
+
+    /// Проверка пометок у упоминаний записей реестра по id текста на сайте
+    pub async fn compliance_by_id(&self, id: String) -> HttpResponse {
+        match self.checker.check_compliance(&id).await {
+            Ok(report) => HttpResponse::Ok().json(report),
+            Err(e) => e.error_response(),
+        }
+    }
+
+    /// Пометки для записей, упомянутых в тексте или статье `id`, и исправленный текст
+    pub async fn fix(
+        &self,
+        text: Option<String>,
+        id: Option<String>,
+        templates: LabelTemplates,
+    ) -> HttpResponse {
+        let fixed = match (text, id) {
+            (Some(text), None) => self.checker.fix_text(&text, &templates).await,
+            (None, Some(id)) => self.checker.fix_article(&id, &templates).await,
+            _ => Err(ServiceError::InvalidInput(
+                "Нужно передать либо text, либо id".to_string(),
+            )),
+        };
+        match fixed {
+            Ok(fixed) => HttpResponse::Ok().json(fixed),
+            Err(e) => e.error_response(),
+        }
+    }
+
+    /// Разбор сравнения имени из текста с записью реестра: все формы имени,
+    /// сходство, расстояния, пороги и итоговое решение
+    pub async fn explain(&self, entity: Entity, record_id: i64) -> HttpResponse {
+        match self.checker.explain(entity, record_id).await {
+            Ok(explanation) => HttpResponse::Ok().json(explanation),
+            Err(e) => e.error_response(),
+        }
+    }
+
+    /// Применение изменений базы данных к списку имен в памяти
+    pub async fn update_warning_names(&self) -> Result<UpdateStats, ServiceError> {
+        let new_warning_names = self.database.lock().await.get_all()?;
+        self.checker.change_warning_names(new_warning_names).await
+    }
+
+    /// Добавление имен: embedding считается на сервере, запись сначала сохраняется
+    /// в базу данных, затем попадает в индекс в памяти, чтобы следующий `/update`
+    /// не потерял добавленные имена. Запись, сохраненная в базу, но не попавшая
+    /// в индекс, возвращается ошибкой вместе с id
+    pub async fn add_warning_names(&self, names: Vec<ImportedRecord>) -> HttpResponse {
+        let mut results = Vec::with_capacity(names.len());
+        let mut added = Vec::new();
+
+        for name in names {
+            let record_name = name.name.clone();
+            let mut record = match build_record(&self.vectorizer, name).await {
+                Ok(r) => r,
+                Err(e) => {
+                    results.push(AddResult::failed(record_name, e));
                     continue;
                 }
-                Ok(inos) => return HttpResponse::Ok().json(inos),
+            };
+
+            match self.database.lock().await.upsert(&record) {
+                Ok(id) => {
+                    record.id = id;
+                    results.push(AddResult::added(record.name.clone(), id));
+                    added.push(record);
+                }
+                Err(e) => results.push(AddResult::failed(record.name, e.into())),
+            }
+        }
+
+        if !added.is_empty() {
+            let mut errors = self.checker.add_warning_names(added).await;
+            for result in &mut results {
+                if let Some(id) = result.id
+                    && let Some(e) = errors.remove(&id)
+                {
+                    *result = AddResult::not_indexed(result.name.clone(), id, e);
+                }
             }
         }
+        HttpResponse::Ok().json(AddResponse { results })
     }
 
-    /// Проверка наличия запрещенных имен по тексту
-    pub async fn check_by_text(&self, text: String, need_full_data: bool) -> HttpResponse {
-        let mut i: u8 = 0;
-        loop {
-            match self
-                .checker
-                .lock()
-                .await
-                .get_inos_from_text(&text, need_full_data)
-                .await
-            {
-                Err(e) => {
-                    i += 1;
+    /// Импорт выгрузки реестра в базу данных и обновление списка в памяти.
+    ///
+    /// Embedding считаются без блокировки базы данных, она блокируется только
+    /// на чтение текущих записей и на запись результата
+    pub async fn import_registry(&self, req: ImportRequest) -> HttpResponse {
+        let record_type = req.record_type.unwrap_or_default();
+        if let Err(e) = record_type.validate() {
+            return e.error_response();
+        }
+        let path = match self.import_path(&req.path) {
+            Ok(p) => p,
+            Err(e) => return e.error_response(),
+        };
+        let records = match MinjustXlsx::new(record_type).read_records(&path) {
+            Ok(r) => r,
+            Err(e) => return ServiceError::InvalidInput(format!("{e}")).error_response(),
+        };
 
-                    if i >= 4 {
-                        let err = ErrorS {
-                            error: format!("{e}"),
-                        };
-                        return HttpResponse::InternalServerError().json(err);
-                    }
+        let existing = match self.database.lock().await.get_all() {
+            Ok(r) => r,
+            Err(e) => return ServiceError::from(e).error_response(),
+        };
+        let prepared = prepare_import(existing, &self.vectorizer, records).await;
+        let report = apply_import(&*self.database.lock().await, prepared);
 
-                    eprintln!("{e}");
-                    sleep(Duration::from_secs(1)).await;
-                    continue;
+        match report {
+            Ok(report) => {
+                if let Err(e) = self.update_warning_names().await {
+                    return e.error_response();
                 }
-                Ok(inos) => return HttpResponse::Ok().json(inos),
+                HttpResponse::Ok().json(report)
             }
+            Err(e) => ServiceError::from(e).error_response(),
         }
     }
 
-    pub async fn update_warning_names(&self) -> Result<(), Error> {
-        let new_warning_names = self.database.lock().await.get_all()?;
-        self.checker
-            .lock()
+    /// Добавление одной записи в индекс в памяти после изменения в базе данных
+    async fn index_record(&self, record: Record) -> Result<(), ServiceError> {
+        let id = record.id;
+        match self
+            .checker
+            .add_warning_names(vec![record])
             .await
-            .change_warning_names(new_warning_names)
-            .await;
-        Ok(())
+            .remove(&id)
+        {
+            Some(e) => Err(ServiceError::Internal(e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Путь к выгрузке внутри каталога импорта. Абсолютные пути и `..`
+    /// отклоняются до обращения к файловой системе
+    fn import_path(&self, path: &str) -> Result<PathBuf, ServiceError> {
+        let path = Path::new(path);
+        let inside = path.components().all(|c| matches!(c, Component::Normal(_)));
+        if !inside || path.as_os_str().is_empty() {
+            return Err(ServiceError::InvalidInput(
+                "path должен быть именем файла в каталоге импорта".to_string(),
+            ));
+        }
+        Ok(self.import_dir.join(path))
     }
 
-    pub async fn add_warning_names(&self, names: Vec<Record>) -> HttpResponse {
-        self.checker.lock().await.add_warning_names(names).await;
-        HttpResponse::Ok().finish()
+    /// Пометка записи как исключенной, возврат в реестр или полное удаление по id.
+    /// Список имен и индекс в памяти обновляются только для этой записи
+    pub async fn change_record(&self, id: i64, action: RecordAction) -> HttpResponse {
+        // None – записи нет, Some(None) – запись удалена, Some(Some(r)) – запись изменена
+        let changed = {
+            let db = self.database.lock().await;
+            match action {
+                RecordAction::Remove => db.mark_removed(id),
+                RecordAction::Restore => db.restore(id),
+                RecordAction::Delete => db.delete(id),
+            }
+            .and_then(|found| match (found, &action) {
+                (false, _) => Ok(None),
+                (true, RecordAction::Delete) => Ok(Some(None)),
+                (true, _) => db.get(id).map(Some),
+            })
+        };
+
+        match changed {
+            Ok(None) => ServiceError::NotFound(format!("Запись {id} не найдена")).error_response(),
+            Ok(Some(Some(record))) => match self.index_record(record).await {
+                Ok(()) => HttpResponse::Ok().finish(),
+                Err(e) => e.error_response(),
+            },
+            Ok(Some(None)) => {
+                self.checker.remove_warning_name(id).await;
+                HttpResponse::Ok().finish()
+            }
+            Err(e) => ServiceError::from(e).error_response(),
+        }
     }
+
+    /// Изменение записи по id. Embedding имени и псевдонимов пересчитываются, если
+    /// изменилось имя; embedding псевдонимов – и если изменился их список `aliases`.
+    /// Без `aliases` у прежнего имени псевдонимы остаются прежними
+    pub async fn update_record(&self, id: i64, req: ImportedRecord) -> HttpResponse {
+        if let Err(e) = req.record_type.validate() {
+            return e.error_response();
+        }
+        let existing = match self.database.lock().await.get(id) {
+            Ok(Some(r)) => r,
+            Ok(None) => {
+                return ServiceError::NotFound(format!("Запись {id} не найдена")).error_response();
+            }
+            Err(e) => {
+                return ServiceError::from(e).error_response();
+            }
+        };
+
+        let new_aliases = req
+            .aliases
+            .as_deref()
+            .map(|aliases| alias_names(&req.name, Some(aliases)))
+            .filter(|names| !names.iter().eq(existing.aliases.iter().map(|a| &a.name)));
+        let mut record = if existing.name != req.name {
+            match build_record(&self.vectorizer, req).await {
+                Ok(r) => r,
+                Err(e) => {
+                    return e.error_response();
+                }
+            }
+        } else if let Some(names) = new_aliases {
+            match embed_alias_names(&self.vectorizer, names).await {
+                Ok(aliases) => Record {
+                    record_type: req.record_type,
+                    is_removed: req.is_removed,
+                    aliases,
+                    ..existing
+                },
+                Err(e) => {
+                    return e.error_response();
+                }
+            }
+        } else {
+            Record {
+                record_type: req.record_type,
+                is_removed: req.is_removed,
+                ..existing
+            }
+        };
+        record.id = id;
+
+        let updated = {
+            let db = self.database.lock().await;
+            match db.find_id(&record.name, &record.record_type) {
+                Ok(Some(other)) if other != id => {
+                    return ServiceError::InvalidInput(format!(
+                        "Запись с таким именем и типом уже есть: {other}"
+                    ))
+                    .error_response();
+                }
+                Ok(_) => db.update(&record).and_then(|_| db.get(id)),
+                Err(e) => Err(e),
+            }
+        };
+        match updated {
+            Ok(Some(record)) => {
+                let info = RecordInfo::from(&record);
+                match self.index_record(record).await {
+                    Ok(()) => HttpResponse::Ok().json(info),
+                    Err(e) => e.error_response(),
+                }
+            }
+            Ok(None) => ServiceError::NotFound(format!("Запись {id} не найдена")).error_response(),
+            Err(e) => ServiceError::from(e).error_response(),
+        }
+    }
+
+    /// Список записей реестра с фильтрами, поиском по имени и постраничным выводом.
+    ///
+    /// Без поиска страница читается из базы данных. Поиск нечеткий, поэтому
+    /// при нем читаются имена и псевдонимы всех записей по фильтру, без embedding
+    pub async fn list_records(&self, query: RecordsQuery) -> HttpResponse {
+        let filter = RecordFilter {
+            record_type: query.record_type,
+            is_removed: query.is_removed,
+        };
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .clamp(1, MAX_PER_PAGE);
+        let page = query.page.unwrap_or(1).max(1);
+        let offset = (page - 1).saturating_mul(per_page);
+
+        let Some(search) = query.q.as_deref().map(str::to_lowercase) else {
+            let db = self.database.lock().await;
+            let page_records = db
+                .count(&filter)
+                .and_then(|total| Ok((total, db.list(&filter, offset, Some(per_page))?)));
+            return match page_records {
+                Ok((total, records)) => HttpResponse::Ok().json(RecordsPage {
+                    total,
+                    page,
+                    per_page,
+                    records,
+                }),
+                Err(e) => ServiceError::from(e).error_response(),
+            };
+        };
+
+        let records = match self.database.lock().await.list(&filter, 0, None) {
+            Ok(r) => r,
+            Err(e) => {
+                return ServiceError::from(e).error_response();
+            }
+        };
+        let max_distance = query.max_distance.unwrap_or(DEFAULT_SEARCH_DISTANCE);
+        let mut found: Vec<(usize, RecordInfo)> = records
+            .into_iter()
+            .filter_map(|r| {
+                search_distance(&search, &r)
+                    .filter(|d| *d <= max_distance)
+                    .map(|d| (d, r))
+            })
+            .collect();
+        found.sort_by_key(|(d, r)| (*d, r.id));
+
+        let total = found.len();
+        let page_records = found
+            .into_iter()
+            .skip(offset)
+            .take(per_page)
+            .map(|(_, r)| r)
+            .collect();
+
+        HttpResponse::Ok().json(RecordsPage {
+            total,
+            page,
+            per_page,
+            records: page_records,
+        })
+    }
+
+    /// Запись реестра по id
+    pub async fn get_record(&self, id: i64) -> HttpResponse {
+        match self.database.lock().await.get(id) {
+            Ok(Some(record)) => HttpResponse::Ok().json(RecordInfo::from(&record)),
+            Ok(None) => ServiceError::NotFound(format!("Запись {id} не найдена")).error_response(),
+            Err(e) => ServiceError::from(e).error_response(),
+        }
+    }
+}
+
+/// Расстояние от строки поиска до имени записи: 0 для подстроки имени или псевдонима,
+/// иначе минимальный `unordered_levenshtein` до имени и псевдонимов
+fn search_distance(query: &str, record: &RecordInfo) -> Option<usize> {
+    std::iter::once(&record.name)
+        .chain(&record.aliases)
+        .map(|name| {
+            let name = name.to_lowercase();
+            if name.contains(query) {
+                0
+            } else {
+                unordered_levenshtein(query, &name)
+            }
+        })
+        .min()
 }
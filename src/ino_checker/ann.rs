@@ -1,13 +1,45 @@
 use crate::db::model::Record;
-use anda_db_hnsw::{DistanceMetric, HnswConfig, HnswIndex};
+use anda_db_hnsw::{BoxError, DistanceMetric, HnswConfig, HnswIndex, serialize_node};
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::ErrorKind;
+use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Под номер псевдонима в ID точки отводятся младшие биты
+const ALIAS_BITS: u32 = 8;
+const MAX_ALIASES: usize = (1 << ALIAS_BITS) - 1;
+
+/// Индекс имен и псевдонимов записей.
+///
+/// У каждого клона свой граф HNSW: клон получает копию графа, поэтому его
+/// изменения не видны снимкам реестра, которые еще читают оригинал
 pub struct ANNIndex {
     index: HnswIndex,
-    records: Vec<Record>,
+    /// Последние метаданные графа в CBOR. `store_metadata()` записывает их,
+    /// только если граф изменился с прошлого вызова, поэтому они хранятся здесь
+    metadata: Mutex<Vec<u8>>,
+    /// Записи по id из базы данных
+    records: HashMap<i64, Record>,
     dim: usize,
 }
 
+/// Граф HNSW в сериализованном виде: метаданные, список id и узлы
+struct GraphParts {
+    metadata: Vec<u8>,
+    ids: Vec<u8>,
+    nodes: HashMap<u64, Vec<u8>>,
+}
+
+/// Результат поиска: запись и псевдоним, через который она найдена
+pub struct IndexMatch {
+    pub record: Record,
+    pub alias: Option<String>,
+    /// Вектор найденной точки – имени или псевдонима
+    pub embedding: Vec<f32>,
+}
+
 impl ANNIndex {
     /// name — любое имя индекса; dim — размерность embedding
     pub fn new(name: impl Into<String>, dim: usize) -> Self {
@@ -18,50 +50,372 @@ impl ANNIndex {
         };
         // при желании: config.ef_search = 64; config.ef_construction = 200; и т.д.
 
-        let index = HnswIndex::new(name.into(), Some(config));
         Self {
+            index: HnswIndex::new(name.into(), Some(config)),
+            metadata: Mutex::new(Vec::new()),
+            records: HashMap::new(),
+            dim,
+        }
+    }
+
+    /// Построение индекса по списку записей. Записи, которые не удалось добавить,
+    /// пропускаются и возвращаются ошибками
+    pub fn from_records(
+        name: impl Into<String>,
+        dim: usize,
+        records: Vec<Record>,
+    ) -> (Self, Vec<String>) {
+        let mut index = Self::new(name, dim);
+        let mut errors = Vec::new();
+        for rec in records {
+            let (id, name) = (rec.id, rec.name.clone());
+            if let Err(e) = index.add(rec) {
+                errors.push(format!("Запись {id} ({name}): {e}"));
+            }
+        }
+        (index, errors)
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn contains(&self, id: i64) -> bool {
+        self.records.contains_key(&id)
+    }
+
+    /// Загрузка индекса из файла, сохраненного `save()`.
+    ///
+    /// `None`, если файла нет, он построен для другого набора записей
+    /// или другой размерности – тогда индекс нужно построить заново
+    pub fn load(
+        path: &str,
+        dim: usize,
+        records: Vec<Record>,
+    ) -> Result<Option<Self>, anyhow::Error> {
+        let data = match fs::read(path) {
+            Ok(d) => d,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut reader = &data[..];
+
+        if read_u64(&mut reader)? != records_checksum(records.iter()) {
+            return Ok(None);
+        }
+        let metadata = read_bytes(&mut reader)?.to_vec();
+        let ids = read_bytes(&mut reader)?.to_vec();
+        let mut nodes = HashMap::new();
+        for _ in 0..read_u64(&mut reader)? {
+            let id = read_u64(&mut reader)?;
+            nodes.insert(id, read_bytes(&mut reader)?.to_vec());
+        }
+
+        let index = Self::from_parts(
+            GraphParts {
+                metadata,
+                ids,
+                nodes,
+            },
+            records.into_iter().map(|r| (r.id, r)).collect(),
+            dim,
+        )?;
+        if index.index.dimension() != dim {
+            return Ok(None);
+        }
+        Ok(Some(index))
+    }
+
+    /// Сохранение индекса в файл вместе с контрольной суммой набора записей.
+    ///
+    /// Формат: контрольная сумма, метаданные HNSW, список id и узлы,
+    /// каждый блок с длиной впереди. Файл пишется во временный и переименовывается
+    pub fn save(&self, path: &str) -> Result<(), anyhow::Error> {
+        let parts = self.to_parts()?;
+        let mut data = Vec::new();
+        data.extend(records_checksum(self.records.values()).to_le_bytes());
+        write_bytes(&mut data, &parts.metadata);
+        write_bytes(&mut data, &parts.ids);
+        data.extend((parts.nodes.len() as u64).to_le_bytes());
+        for (id, node) in parts.nodes {
+            data.extend(id.to_le_bytes());
+            write_bytes(&mut data, &node);
+        }
+
+        let tmp_path = format!("{path}.tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    fn to_parts(&self) -> Result<GraphParts, anyhow::Error> {
+        let metadata = {
+            let mut cached = self.metadata.lock().unwrap();
+            let mut fresh = Vec::new();
+            if self.index.store_metadata(&mut fresh, now_ms())? {
+                *cached = fresh;
+            }
+            cached.clone()
+        };
+        let mut ids = Vec::new();
+        self.index.store_ids(&mut ids)?;
+        let mut nodes = HashMap::new();
+        for id in self.index.node_ids() {
+            nodes.insert(id, self.index.get_node_with(id, serialize_node)?);
+        }
+        Ok(GraphParts {
+            metadata,
+            ids,
+            nodes,
+        })
+    }
+
+    fn from_parts(
+        mut parts: GraphParts,
+        records: HashMap<i64, Record>,
+        dim: usize,
+    ) -> Result<Self, anyhow::Error> {
+        // Узлы уже в памяти, поэтому future завершается без ожидания
+        let index = futures::executor::block_on(HnswIndex::load_all(
+            &parts.metadata[..],
+            &parts.ids[..],
+            async |id| Ok::<_, BoxError>(parts.nodes.remove(&id)),
+        ))?;
+        Ok(Self {
             index,
-            records: Vec::new(),
+            metadata: Mutex::new(parts.metadata),
+            records,
             dim,
+        })
+    }
+
+    /// Добавление записи вместе с псевдонимами.
+    ///
+    /// ID точки в индексе = `record.id << 8 | n`, где `n` – 0 для основного имени
+    /// и номер псевдонима + 1. Запись с тем же id заменяется.
+    ///
+    /// Все векторы проверяются до изменения индекса. Если вставка все же не удалась,
+    /// в индекс возвращается прежняя запись с этим id. Запись без id (еще не
+    /// сохраненная в базу данных) отклоняется: такие записи заменяли бы друг друга
+    pub fn add(&mut self, rec: Record) -> Result<(), anyhow::Error> {
+        if rec.id <= 0 {
+            return Err(anyhow::anyhow!(
+                "Запись {} не сохранена в базу данных: id {}",
+                rec.name,
+                rec.id
+            ));
         }
+        let vectors = rec
+            .embedding
+            .iter()
+            .chain(rec.aliases.iter().filter_map(|a| a.embedding.as_ref()));
+        for embedding in vectors {
+            self.check_vector(embedding)?;
+        }
+
+        let old = self.remove(rec.id);
+        if let Err(e) = self.insert_points(&rec) {
+            self.remove_points(&rec);
+            if let Some(old) = old {
+                match self.insert_points(&old) {
+                    Ok(()) => {
+                        self.records.insert(old.id, old);
+                    }
+                    Err(_) => self.remove_points(&old),
+                }
+            }
+            return Err(e);
+        }
+        self.records.insert(rec.id, rec);
+        Ok(())
     }
 
-    /// Добавление записи. ID в индексе = позиция в векторе `records`
-    pub fn add(&mut self, rec: Record) {
-        assert!(
-            rec.embedding.len() == self.dim,
-            "Embedding len {} != dim {}",
-            rec.embedding.len(),
-            self.dim
-        );
-        let id = self.records.len() as u64;
-        self.index
-            .insert_f32(id, rec.embedding.clone(), now_ms())
-            .expect("insert_f32 failed");
-        self.records.push(rec);
+    /// Удаление записи и всех ее псевдонимов из индекса
+    pub fn remove(&mut self, id: i64) -> Option<Record> {
+        let rec = self.records.remove(&id)?;
+        self.remove_points(&rec);
+        Some(rec)
+    }
+
+    /// Изменение флага `is_removed` без перестройки точек индекса
+    pub fn set_removed(&mut self, id: i64, is_removed: bool) -> bool {
+        match self.records.get_mut(&id) {
+            Some(rec) => {
+                rec.is_removed = is_removed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Хеш содержимого и флаг `is_removed` каждой записи – для сравнения с базой данных
+    pub fn fingerprints(&self) -> HashMap<i64, (u64, bool)> {
+        self.records
+            .iter()
+            .map(|(id, rec)| (*id, (content_hash(rec), rec.is_removed)))
+            .collect()
+    }
+
+    /// Вектор нужной размерности без NaN и бесконечностей
+    fn check_vector(&self, embedding: &[f32]) -> Result<(), anyhow::Error> {
+        if embedding.len() != self.dim {
+            return Err(anyhow::anyhow!(
+                "Embedding len {} != dim {}",
+                embedding.len(),
+                self.dim
+            ));
+        }
+        if !embedding.iter().all(|v| v.is_finite()) {
+            return Err(anyhow::anyhow!("Embedding contains NaN or infinity"));
+        }
+        Ok(())
+    }
+
+    fn insert_points(&mut self, rec: &Record) -> Result<(), anyhow::Error> {
+        let now = now_ms();
+        if let Some(embedding) = &rec.embedding {
+            self.index
+                .insert_f32(point_id(rec.id, None), embedding.clone(), now)?;
+        }
+        for (i, alias) in rec.aliases.iter().take(MAX_ALIASES).enumerate() {
+            if let Some(embedding) = &alias.embedding {
+                self.index
+                    .insert_f32(point_id(rec.id, Some(i)), embedding.clone(), now)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_points(&mut self, rec: &Record) {
+        let now = now_ms();
+        if rec.embedding.is_some() {
+            self.index.remove(point_id(rec.id, None), now);
+        }
+        for (i, alias) in rec.aliases.iter().take(MAX_ALIASES).enumerate() {
+            if alias.embedding.is_some() {
+                self.index.remove(point_id(rec.id, Some(i)), now);
+            }
+        }
     }
 
     /// Поиск k ближайших. Возвращает только не удалённые записи.
-    pub fn search(&self, query: &[f32], k: usize) -> Vec<Record> {
-        assert!(
-            query.len() == self.dim,
-            "Query len {} != dim {}",
-            query.len(),
-            self.dim
-        );
-        let matches = self.index.search_f32(query, k).expect("search_f32 failed");
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<IndexMatch>, anyhow::Error> {
+        self.check_vector(query)?;
+        let matches = self.index.search_f32(query, k)?;
 
         // `search_f32` возвращает (id, distance), отсортировано по возрастанию distance
-        matches
+        Ok(matches
             .into_iter()
             .filter_map(|(id, _dist)| {
-                self.records
-                    .get(id as usize)
-                    .filter(|r| !r.is_removed)
-                    .cloned()
+                let (record_id, alias_pos) = split_point_id(id);
+                let record = self.records.get(&record_id).filter(|r| !r.is_removed)?;
+                let (alias, embedding) = match alias_pos {
+                    Some(i) => {
+                        let alias = record.aliases.get(i)?;
+                        (Some(alias.name.clone()), alias.embedding.clone()?)
+                    }
+                    None => (None, record.embedding.clone()?),
+                };
+                Some(IndexMatch {
+                    record: record.clone(),
+                    alias,
+                    embedding,
+                })
             })
-            .collect()
+            .collect())
+    }
+}
+
+impl Clone for ANNIndex {
+    /// Копия со своим графом. Если граф не удалось скопировать, копия строится
+    /// заново по записям – это дольше, но результат тот же
+    fn clone(&self) -> Self {
+        let records = self.records.clone();
+        match self
+            .to_parts()
+            .and_then(|parts| Self::from_parts(parts, records, self.dim))
+        {
+            Ok(index) => index,
+            Err(e) => {
+                eprintln!("Failed to copy index, rebuilding: {e}");
+                let records = self.records.values().cloned().collect();
+                let (index, errors) = Self::from_records(self.index.name(), self.dim, records);
+                for e in errors {
+                    eprintln!("{e}");
+                }
+                index
+            }
+        }
+    }
+}
+
+/// Хеш содержимого записи без флага `is_removed`: имя, тип, векторы и псевдонимы
+pub fn content_hash(rec: &Record) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    rec.name.hash(&mut hasher);
+    rec.record_type.hash(&mut hasher);
+    if let Some(embedding) = &rec.embedding {
+        hash_vector(embedding, &mut hasher);
+    }
+    for alias in &rec.aliases {
+        alias.name.hash(&mut hasher);
+        if let Some(embedding) = &alias.embedding {
+            hash_vector(embedding, &mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Контрольная сумма набора записей: id и содержимое без флага `is_removed`.
+///
+/// Считается через `DefaultHasher`, поэтому после обновления компилятора может
+/// измениться – тогда индекс просто построится заново
+fn records_checksum<'a>(records: impl Iterator<Item = &'a Record>) -> u64 {
+    let mut hashes: Vec<(i64, u64)> = records.map(|r| (r.id, content_hash(r))).collect();
+    hashes.sort_unstable();
+    let mut hasher = DefaultHasher::new();
+    hashes.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn write_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+    data.extend((bytes.len() as u64).to_le_bytes());
+    data.extend(bytes);
+}
+
+fn read_u64(reader: &mut &[u8]) -> Result<u64, anyhow::Error> {
+    let (head, tail) = reader
+        .split_first_chunk::<8>()
+        .ok_or_else(|| anyhow::anyhow!("Index file is truncated"))?;
+    *reader = tail;
+    Ok(u64::from_le_bytes(*head))
+}
+
+fn read_bytes<'a>(reader: &mut &'a [u8]) -> Result<&'a [u8], anyhow::Error> {
+    let len = read_u64(reader)? as usize;
+    if reader.len() < len {
+        return Err(anyhow::anyhow!("Index file is truncated"));
     }
+    let (head, tail) = reader.split_at(len);
+    *reader = tail;
+    Ok(head)
+}
+
+fn hash_vector(v: &[f32], hasher: &mut DefaultHasher) {
+    for f in v {
+        f.to_bits().hash(hasher);
+    }
+}
+
+fn point_id(record_id: i64, alias: Option<usize>) -> u64 {
+    let slot = alias.map(|i| i as u64 + 1).unwrap_or(0);
+    ((record_id as u64) << ALIAS_BITS) | slot
+}
+
+fn split_point_id(id: u64) -> (i64, Option<usize>) {
+    let record_id = (id >> ALIAS_BITS) as i64;
+    let slot = (id & MAX_ALIASES as u64) as usize;
+    (record_id, slot.checked_sub(1))
 }
 
 fn now_ms() -> u64 {
@@ -70,3 +424,105 @@ fn now_ms() -> u64 {
         .expect("time went backwards")
         .as_millis() as u64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::model::RecordCategory;
+
+    fn record(id: i64, name: &str, embedding: Vec<f32>) -> Record {
+        Record {
+            id,
+            name: name.to_string(),
+            record_type: RecordCategory::ForeignAgent,
+            embedding: Some(embedding),
+            is_removed: false,
+            aliases: Vec::new(),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn invalid_vector_keeps_previous_record() {
+        let mut index = ANNIndex::new("test", 3);
+        index.add(record(1, "Иванов", vec![1.0, 0.0, 0.0])).unwrap();
+
+        assert!(
+            index
+                .add(record(1, "Петров", vec![f32::NAN, 0.0, 0.0]))
+                .is_err()
+        );
+        assert!(index.add(record(1, "Петров", vec![1.0, 0.0])).is_err());
+
+        let found = index.search(&[1.0, 0.0, 0.0], 1).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].record.name, "Иванов");
+    }
+
+    #[test]
+    fn unsaved_records_are_rejected() {
+        let mut index = ANNIndex::new("test", 3);
+        assert!(index.add(record(0, "Иванов", vec![1.0, 0.0, 0.0])).is_err());
+        assert!(!index.contains(0));
+        assert!(index.search(&[1.0, 0.0, 0.0], 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn records_without_embedding_are_kept() {
+        let mut index = ANNIndex::new("test", 3);
+        let latin = Record {
+            embedding: None,
+            ..record(2, "Meduza", Vec::new())
+        };
+        index.add(latin.clone()).unwrap();
+        index.add(latin).unwrap();
+        assert!(index.contains(2));
+        assert!(index.search(&[1.0, 0.0, 0.0], 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn clone_taken_before_update_keeps_its_points() {
+        let mut index = ANNIndex::new("test", 3);
+        index.add(record(1, "Иванов", vec![1.0, 0.0, 0.0])).unwrap();
+        let old = index.clone();
+
+        index.remove(1);
+        let mut updated = record(2, "Петров", vec![1.0, 0.1, 0.0]);
+        updated.aliases = vec![crate::db::model::Alias {
+            name: "Петя".to_string(),
+            embedding: Some(vec![0.9, 0.0, 0.1]),
+        }];
+        index.add(updated).unwrap();
+
+        let found = old.search(&[1.0, 0.0, 0.0], 3).unwrap();
+        let names: Vec<&str> = found.iter().map(|m| m.record.name.as_str()).collect();
+        assert_eq!(names, ["Иванов"]);
+        let found = index.search(&[1.0, 0.0, 0.0], 3).unwrap();
+        assert!(found.iter().all(|m| m.record.id == 2));
+        assert_eq!(found.len(), 2);
+    }
+
+    #[test]
+    fn saved_index_is_loaded_only_for_same_records() {
+        let path = std::env::temp_dir().join(format!("ann-test-{}.hnsw", std::process::id()));
+        let path = path.to_str().unwrap();
+        let records = vec![
+            record(1, "Иванов", vec![1.0, 0.0, 0.0]),
+            record(2, "Петров", vec![0.0, 1.0, 0.0]),
+        ];
+        let (index, errors) = ANNIndex::from_records("test", 3, records.clone());
+        assert!(errors.is_empty());
+        index.save(path).unwrap();
+
+        let loaded = ANNIndex::load(path, 3, records.clone()).unwrap().unwrap();
+        let found = loaded.search(&[0.0, 1.0, 0.0], 1).unwrap();
+        assert_eq!(found[0].record.name, "Петров");
+
+        let mut changed = records.clone();
+        changed[1].name = "Сидоров".to_string();
+        assert!(ANNIndex::load(path, 3, changed).unwrap().is_none());
+        assert!(ANNIndex::load(path, 4, records).unwrap().is_none());
+        fs::remove_file(path).unwrap();
+    }
+}
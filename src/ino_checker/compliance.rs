@@ -0,0 +1,329 @@
+use crate::db::model::RecordCategory;
+use crate::ino_checker::model::{LabelStatus, MentionCompliance, WarningName};
+use crate::ino_checker::scanner::normalize;
+use regex::Regex;
+use std::ops::Range;
+
+/// Сколько символов до упоминания просматривается в поиске пометки
+const BEFORE_CHARS: usize = 60;
+/// Сколько символов после упоминания просматривается в поиске пометки
+const AFTER_CHARS: usize = 150;
+
+/// Фразы пометки одного вида реестра, в нижнем регистре и без `ё`
+struct LabelPatterns {
+    category: RecordCategory,
+    /// Формулировка, которая считается пометкой: `признан иностранным агентом`
+    full: Regex,
+    /// Неполная или разговорная: `иноагент`, `запрещена в России`
+    partial: Regex,
+}
+
+/// Проверка пометок у упоминаний: рядом с каждым именем из реестра должна
+/// стоять фраза о его статусе – в том же абзаце или в сноске со звездочкой
+pub struct ComplianceChecker {
+    labels: Vec<LabelPatterns>,
+    /// Звездочки сразу после имени: `Иван Иванов*`
+    asterisks: Regex,
+}
+
+impl ComplianceChecker {
+    pub fn new() -> Self {
+        let forbidden = |adjective: &str| {
+            format!(
+                r"{adjective}\w*[^.\n]{{0,80}}запрещен\w*|запрещен\w*[^.\n]{{0,80}}{adjective}\w*"
+            )
+        };
+        let banned_in_russia = r"запрещен\w*\s+(?:на\s+территории\s+)?(?:в\s+)?(?:рф|росси\w*)";
+        let labels = vec![
+            LabelPatterns {
+                category: RecordCategory::ForeignAgent,
+                full: Regex::new(r"иностранн\w*[\s-]+агент\w*").unwrap(),
+                partial: Regex::new(r"иноагент\w*").unwrap(),
+            },
+            LabelPatterns {
+                category: RecordCategory::Undesirable,
+                full: Regex::new(
+                    r"нежелательн\w*\s+(?:на\s+территории\s+\w+(?:\s+федерации)?\s+)?организаци\w*|признан\w*\s+нежелательн\w*",
+                )
+                .unwrap(),
+                partial: Regex::new(r"нежелательн\w*").unwrap(),
+            },
+            LabelPatterns {
+                category: RecordCategory::Extremist,
+                full: Regex::new(&forbidden("экстремистск")).unwrap(),
+                partial: Regex::new(&format!(r"экстремистск\w*|{banned_in_russia}")).unwrap(),
+            },
+            LabelPatterns {
+                category: RecordCategory::Terrorist,
+                full: Regex::new(&forbidden("террористическ")).unwrap(),
+                partial: Regex::new(&format!(r"террористическ\w*|{banned_in_russia}")).unwrap(),
+            },
+        ];
+        ComplianceChecker {
+            labels,
+            asterisks: Regex::new(r"^[»\x22)]*(\*+)").unwrap(),
+        }
+    }
+
+    /// Пометка у каждого упоминания каждого имени с записями реестра.
+    /// Упоминания в `warnings` – из проверки того же текста, `text` – он же
+    /// без разметки: пометка ищется в нем по `Mention::range`.
+    ///
+    /// Для имени с записями разных видов реестра упоминание проверяется
+    /// отдельно для каждого вида. Исключенные записи и свои виды реестра
+    /// пометки не требуют
+    pub fn check(&self, text: &str, warnings: &[WarningName]) -> Vec<MentionCompliance> {
+        let normalized = normalize(text);
+        let mut mentions = Vec::new();
+
+        for warning in warnings {
+            let mut categories: Vec<&RecordCategory> = Vec::new();
+            for doc in &warning.docs {
+                if categories.contains(&&doc.status) {
+                    continue;
+                }
+                categories.push(&doc.status);
+
+                let labels = self.labels.iter().find(|l| l.category == doc.status);
+                for mention in &warning.mentions {
+                    let (label_status, label) = match labels {
+                        Some(labels) if !doc.is_removed => {
+                            self.label_near(text, &normalized, mention.range.clone(), labels)
+                        }
+                        _ => (LabelStatus::NotRequired, None),
+                    };
+                    mentions.push(MentionCompliance {
+                        name: mention.text.clone(),
+                        start: mention.start,
+                        end: mention.end,
+                        start_utf16: mention.start_utf16,
+                        end_utf16: mention.end_utf16,
+                        range: mention.range.clone(),
+                        context: warning.context.clone(),
+                        record_id: doc.id,
+                        record_name: doc.name.clone(),
+                        status: doc.status.clone(),
+                        label_status,
+                        label,
+                    });
+                }
+            }
+        }
+
+        mentions.sort_by_key(|m| (m.start, m.record_id));
+        mentions
+    }
+
+    /// Пометка рядом с упоминанием `range`: в том же абзаце или в сноске.
+    /// Неполная формулировка рядом или полная в другом месте текста – частичная пометка
+    fn label_near(
+        &self,
+        text: &str,
+        normalized: &str,
+        range: Range<usize>,
+        labels: &LabelPatterns,
+    ) -> (LabelStatus, Option<String>) {
+        let window = label_window(normalized, range.clone());
+        if let Some(m) = labels.full.find(&normalized[window.clone()]) {
+            let found = window.start + m.start()..window.start + m.end();
+            return (LabelStatus::Labeled, Some(text[found].to_string()));
+        }
+        if let Some(footnote) = self.footnote(text, normalized, range.end, labels) {
+            return (LabelStatus::Labeled, Some(footnote));
+        }
+        if let Some(m) = labels.partial.find(&normalized[window.clone()]) {
+            let found = window.start + m.start()..window.start + m.end();
+            return (LabelStatus::PartiallyLabeled, Some(text[found].to_string()));
+        }
+        if let Some(m) = labels.full.find(normalized) {
+            return (
+                LabelStatus::PartiallyLabeled,
+                Some(text[m.range()].to_string()),
+            );
+        }
+        (LabelStatus::Unlabeled, None)
+    }
+
+    /// Сноска для имени со звездочками: строка или предложение, которое начинается
+    /// с тех же звездочек и содержит полную формулировку пометки
+    fn footnote(
+        &self,
+        text: &str,
+        normalized: &str,
+        mention_end: usize,
+        labels: &LabelPatterns,
+    ) -> Option<String> {
+        let caps = self.asterisks.captures(&normalized[mention_end..])?;
+        let stars = caps.get(1)?.as_str();
+        let after = mention_end + caps.get(0)?.end();
+
+        normalized[after..]
+            .match_indices(stars)
+            .map(|(i, _)| after + i)
+            // Те же звездочки, а не начало более длинной сноски и не хвост другой
+            .filter(|&i| {
+                !normalized[i + stars.len()..].starts_with('*') && !normalized[..i].ends_with('*')
+            })
+            .find_map(|i| {
+                let start = i + stars.len();
+                let end = normalized[start..]
+                    .find(['\n', '*'])
+                    .map_or(normalized.len(), |e| start + e);
+                labels
+                    .full
+                    .is_match(&normalized[start..end])
+                    .then(|| text[i..end].trim().to_string())
+            })
+    }
+}
+
+/// Окно поиска пометки: до `BEFORE_CHARS` символов до упоминания и `AFTER_CHARS`
+/// после, не дальше границ абзаца. Абзацы разделены переводами строк: в тексте
+/// из HTML они стоят на месте блочных тегов (`StrippedHtml`)
+fn label_window(text: &str, range: Range<usize>) -> Range<usize> {
+    let paragraph_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let paragraph_end = text[range.end..]
+        .find('\n')
+        .map_or(text.len(), |i| range.end + i);
+
+    let start = text[..range.start]
+        .char_indices()
+        .rev()
+        .nth(BEFORE_CHARS - 1)
+        .map_or(0, |(i, _)| i)
+        .max(paragraph_start);
+    let end = text[range.end..]
+        .char_indices()
+        .nth(AFTER_CHARS)
+        .map_or(text.len(), |(i, _)| range.end + i)
+        .min(paragraph_end);
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ino_checker::model::{DetectionSource, Doc, Mention};
+    use crate::rv::get::StrippedHtml;
+
+    /// Упоминание `nth` вхождения `name` в тексте без разметки
+    fn mention(html: &str, stripped: &StrippedHtml, name: &str, nth: usize) -> Mention {
+        let (offset, _) = stripped.text.match_indices(name).nth(nth).unwrap();
+        let range = offset..offset + name.len();
+        let start = stripped.source_start(range.start);
+        let end = stripped.source_end(range.end);
+        Mention {
+            text: name.to_string(),
+            start,
+            end,
+            start_utf16: html[..start].encode_utf16().count(),
+            end_utf16: html[..end].encode_utf16().count(),
+            range,
+        }
+    }
+
+    fn warning(name: &str, id: i64, mentions: Vec<Mention>) -> WarningName {
+        WarningName {
+            name: name.to_string(),
+            normal_name: name.to_string(),
+            context: String::new(),
+            name_type: "PER".to_string(),
+            docs: vec![Doc {
+                id,
+                status: RecordCategory::ForeignAgent,
+                name: name.to_string(),
+                is_removed: false,
+                similarity: 1.0,
+                distance: 0,
+                debug_distances: None,
+                matched_alias: None,
+            }],
+            source: DetectionSource::Ner,
+            mentions,
+        }
+    }
+
+    /// Статус пометки у каждого упоминания `name` в `html`
+    fn statuses(html: &str, name: &str) -> Vec<LabelStatus> {
+        let stripped = StrippedHtml::new(html);
+        let mentions = (0..stripped.text.matches(name).count())
+            .map(|nth| mention(html, &stripped, name, nth))
+            .collect();
+        ComplianceChecker::new()
+            .check(&stripped.text, &[warning(name, 1, mentions)])
+            .iter()
+            .map(|m| m.label_status)
+            .collect()
+    }
+
+    #[test]
+    fn offsets_are_taken_from_mentions() {
+        let html = "<p><b>Иван Иванов</b>, признанный иностранным агентом</p>\n<p>Петр Петров</p>";
+        let stripped = StrippedHtml::new(html);
+        let warnings = [
+            warning(
+                "Петр Петров",
+                2,
+                vec![mention(html, &stripped, "Петр Петров", 0)],
+            ),
+            warning(
+                "Иван Иванов",
+                1,
+                vec![mention(html, &stripped, "Иван Иванов", 0)],
+            ),
+        ];
+
+        let mentions = ComplianceChecker::new().check(&stripped.text, &warnings);
+        let result: Vec<(i64, usize, usize, LabelStatus)> = mentions
+            .iter()
+            .map(|m| (m.record_id, m.start, m.start_utf16, m.label_status))
+            .collect();
+        let petrov = html.find("Петр").unwrap();
+        assert_eq!(
+            result,
+            [
+                (1, 6, 6, LabelStatus::Labeled),
+                (
+                    2,
+                    petrov,
+                    html[..petrov].encode_utf16().count(),
+                    LabelStatus::PartiallyLabeled
+                ),
+            ]
+        );
+        assert_eq!(mentions[0].end, html.find("</b>").unwrap());
+    }
+
+    #[test]
+    fn label_in_other_paragraph_is_partial() {
+        #[rustfmt::skip]
+        const CASES: &[(&str, LabelStatus)] = &[
+            ("Иван Иванов выступил. Он признан иностранным агентом", LabelStatus::Labeled),
+            ("Иван Иванов выступил.\nОн признан иностранным агентом", LabelStatus::PartiallyLabeled),
+            ("<p>Иван Иванов выступил.</p><p>Он признан иностранным агентом</p>", LabelStatus::PartiallyLabeled),
+            ("<p>Иван Иванов выступил.</p><div>Он признан иностранным агентом</div>", LabelStatus::PartiallyLabeled),
+            ("<p>Иван Иванов выступил. <b>Он признан</b> иностранным агентом</p>", LabelStatus::Labeled),
+            ("<p>Иван Иванов выступил.</p><p>Он иноагент</p>", LabelStatus::Unlabeled),
+        ];
+        for (text, status) in CASES {
+            assert_eq!(statuses(text, "Иван Иванов"), [*status], "{text}");
+        }
+    }
+
+    #[test]
+    fn article_html() {
+        // Разметка, как в тексте статьи с сайта
+        let html = concat!(
+            "<p>Об этом рассказал <a href=\"https://example.com/\">Иван&nbsp;Иванов</a>, ",
+            "признанный в&nbsp;России иностранным агентом.</p>\n",
+            "<p>&laquo;Мы продолжим работу&raquo;, &mdash; добавил Иван&nbsp;Иванов.</p>\n",
+            "<p>На встречу пришел и <strong>Петр Петров</strong>*.</p>\n",
+            "<p><em>* Признан иностранным агентом</em></p>",
+        );
+        assert_eq!(
+            statuses(html, "Иван Иванов"),
+            [LabelStatus::Labeled, LabelStatus::PartiallyLabeled]
+        );
+        assert_eq!(statuses(html, "Петр Петров"), [LabelStatus::Labeled]);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::db::model::RecordCategory;
+use crate::import::kinds::label_template;
+use crate::ino_checker::model::{LabelStatus, MentionCompliance, RequiredLabel};
+use std::collections::HashMap;
+
+/// Шаблоны пометок редакции по видам реестра, заменяют шаблоны по умолчанию
+pub type LabelTemplates = HashMap<RecordCategory, String>;
+
+/// Пометки для всех записей, упомянутых в тексте, и исправленный текст.
+///
+/// Пометка вставляется после первого упоминания записи, если у него нет полной
+/// пометки. `mentions` – упоминания в `html`, отсортированные по смещению;
+/// `plain` – `html` без разметки, в нем упоминание находится по `range`.
+/// Возвращает пометки, исправленный `plain` и исправленный `html`
+pub fn fix(
+    html: &str,
+    plain: &str,
+    mentions: &[MentionCompliance],
+    templates: &LabelTemplates,
+) -> (Vec<RequiredLabel>, String, String) {
+    let mut labels = Vec::new();
+    // Вставки: смещение в байтах и текст
+    let mut plain_inserts: Vec<(usize, String)> = Vec::new();
+    let mut html_inserts: Vec<(usize, String)> = Vec::new();
+
+    for mention in mentions {
+        if labels
+            .iter()
+            .any(|l: &RequiredLabel| l.record_id == mention.record_id)
+        {
+            continue;
+        }
+
+        let label = (mention.label_status != LabelStatus::NotRequired)
+            .then(|| label_text(mention, templates))
+            .flatten();
+        let needs_insert = label.is_some()
+            && matches!(
+                mention.label_status,
+                LabelStatus::Unlabeled | LabelStatus::PartiallyLabeled
+            );
+
+        let mut inserted = false;
+        if let Some(label) = label.as_ref().filter(|_| needs_insert) {
+            // Конец упоминания в HTML – до закрывающего тега, даже если имя
+            // разбито тегами: `<b>Иван</b> Иванов`
+            plain_inserts.push((skip_closing(plain, mention.range.end), label.clone()));
+            html_inserts.push((skip_closing_html(html, mention.end), escape_html(label)));
+            inserted = true;
+        }
+
+        labels.push(RequiredLabel {
+            record_id: mention.record_id,
+            record_name: mention.record_name.clone(),
+            status: mention.status.clone(),
+            mention: mention.name.clone(),
+            start: mention.start,
+            end: mention.end,
+            start_utf16: mention.start_utf16,
+            end_utf16: mention.end_utf16,
+            label_status: mention.label_status,
+            label,
+            inserted,
+        });
+    }
+
+    (
+        labels,
+        apply_inserts(plain, plain_inserts),
+        apply_inserts(html, html_inserts),
+    )
+}
+
+/// Текст пометки по шаблону редакции или по умолчанию
+fn label_text(mention: &MentionCompliance, templates: &LabelTemplates) -> Option<String> {
+    let template = templates
+        .get(&mention.status)
+        .map(String::as_str)
+        .or_else(|| label_template(&mention.status))?;
+
+    let legal = mention.status.legal_label();
+    let mut chars = legal.chars();
+    let legal = chars
+        .next()
+        .map(|c| c.to_lowercase().chain(chars).collect::<String>())
+        .unwrap_or_default();
+    Some(
+        template
+            .replace("{label}", &legal)
+            .replace("{name}", &mention.record_name),
+    )
+}
+
+/// Закрывающие кавычки после имени остаются перед пометкой: `«Мемориал» (...)`
+fn skip_closing(text: &str, pos: usize) -> usize {
+    pos + text[pos..]
+        .chars()
+        .take_while(|c| matches!(c, '»' | '"' | '“'))
+        .map(char::len_utf8)
+        .sum::<usize>()
+}
+
+/// То же в HTML, где кавычки могут стоять за закрывающими тегами: `<i>Мемориал</i>»`.
+/// Пометка встает после последней кавычки, а без кавычек – сразу после имени
+fn skip_closing_html(html: &str, pos: usize) -> usize {
+    let mut end = pos;
+    let mut cur = pos;
+    loop {
+        if html[cur..].starts_with("</")
+            && let Some(tag_end) = html[cur..].find('>')
+        {
+            cur += tag_end + 1;
+            continue;
+        }
+        let quotes_end = skip_closing(html, cur);
+        if quotes_end == cur {
+            return end;
+        }
+        cur = quotes_end;
+        end = cur;
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn apply_inserts(text: &str, mut inserts: Vec<(usize, String)>) -> String {
+    // С конца, чтобы вставки не сдвигали еще не примененные смещения
+    inserts.sort_by_key(|(pos, _)| std::cmp::Reverse(*pos));
+    let mut out = text.to_string();
+    for (pos, insert) in inserts {
+        out.insert_str(pos, &insert);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rv::get::StrippedHtml;
+
+    /// Упоминание `name` записи `record_id`, `nth` по счету в тексте
+    fn mention(
+        html: &str,
+        stripped: &StrippedHtml,
+        name: &str,
+        nth: usize,
+        record_id: i64,
+        label_status: LabelStatus,
+    ) -> MentionCompliance {
+        let (offset, _) = stripped.text.match_indices(name).nth(nth).unwrap();
+        let range = offset..offset + name.len();
+        let start = stripped.source_start(range.start);
+        let end = stripped.source_end(range.end);
+        MentionCompliance {
+            name: name.to_string(),
+            start,
+            end,
+            start_utf16: html[..start].encode_utf16().count(),
+            end_utf16: html[..end].encode_utf16().count(),
+            range,
+            context: String::new(),
+            record_id,
+            record_name: name.to_string(),
+            status: RecordCategory::ForeignAgent,
+            label_status,
+            label: None,
+        }
+    }
+
+    fn templates(template: &str) -> LabelTemplates {
+        HashMap::from([(RecordCategory::ForeignAgent, template.to_string())])
+    }
+
+    #[test]
+    fn label_follows_name_split_by_tags() {
+        let html = "<p><b>Иван</b> Иванов заявил</p>\n<p>Иван Иванов</p>";
+        let stripped = StrippedHtml::new(html);
+        let mentions = [
+            mention(html, &stripped, "Иван Иванов", 0, 1, LabelStatus::Unlabeled),
+            mention(html, &stripped, "Иван Иванов", 1, 1, LabelStatus::Unlabeled),
+        ];
+        let (labels, text, fixed) = fix(html, &stripped.text, &mentions, &templates("*"));
+
+        assert_eq!(labels.len(), 1);
+        assert!(labels[0].inserted);
+        assert_eq!(labels[0].start, html.find("Иван").unwrap());
+        assert_eq!(text, "Иван Иванов* заявил\nИван Иванов");
+        assert_eq!(
+            fixed,
+            "<p><b>Иван</b> Иванов* заявил</p>\n<p>Иван Иванов</p>"
+        );
+    }
+
+    #[test]
+    fn labeled_names_and_closing_quotes() {
+        let html = "<p>«<i>Мемориал</i>» и Петр Петров, иностранный агент</p>";
+        let stripped = StrippedHtml::new(html);
+        let mentions = [
+            mention(
+                html,
+                &stripped,
+                "Мемориал",
+                0,
+                1,
+                LabelStatus::PartiallyLabeled,
+            ),
+            mention(html, &stripped, "Петр Петров", 0, 2, LabelStatus::Labeled),
+        ];
+        let (labels, text, fixed) = fix(
+            html,
+            &stripped.text,
+            &mentions,
+            &templates(" ({label} <{name}>)"),
+        );
+
+        assert_eq!(
+            labels.iter().map(|l| l.inserted).collect::<Vec<_>>(),
+            [true, false]
+        );
+        assert_eq!(
+            labels[1].label.as_deref(),
+            Some(" (иностранный агент <Петр Петров>)")
+        );
+        assert_eq!(
+            text,
+            "«Мемориал» (иностранный агент <Мемориал>) и Петр Петров, иностранный агент"
+        );
+        // Закрывающая кавычка в HTML отделена тегом и все равно остается перед пометкой
+        assert_eq!(
+            fixed,
+            "<p>«<i>Мемориал</i>» (иностранный агент &lt;Мемориал&gt;) и Петр Петров, иностранный агент</p>"
+        );
+    }
+
+    #[test]
+    fn inserts_are_applied_from_the_end() {
+        let inserts = vec![
+            (0, "[".to_string()),
+            (8, "]".to_string()),
+            (4, "|".to_string()),
+        ];
+        assert_eq!(apply_inserts("Иван", inserts), "[Ив|ан]");
+    }
+}
@@ -1,5 +1,9 @@
 use crate::db::model::Record;
+use crate::error::model::ServiceError;
+use crate::ino_checker::fixer::LabelTemplates;
 use crate::ino_checker::model;
+use crate::ner::model::Entity;
+use std::collections::HashMap;
 
 pub trait BasicChecker {
     /// Получение списка запрещенных имен по тексту
@@ -7,7 +11,7 @@ pub trait BasicChecker {
         &self,
         text: &str,
         need_full_data: bool,
-    ) -> Result<model::WarningNames, anyhow::Error>;
+    ) -> Result<model::WarningNames, ServiceError>;
 
     /// Получение списка запрещенных имен по id
     ///
@@ -16,17 +20,65 @@ pub trait BasicChecker {
         &self,
         news_id: &str,
         need_full_data: bool,
-    ) -> Result<model::WarningNames, anyhow::Error>;
+    ) -> Result<model::WarningNames, ServiceError>;
 
-    /// Изменение списка запрещенных имен
-    async fn change_warning_names(&self, new_warning_names: Vec<Record>);
+    /// Проверка пометок у всех упоминаний записей реестра в тексте
+    async fn check_compliance_from_text(
+        &self,
+        text: &str,
+    ) -> Result<model::ComplianceReport, ServiceError>;
+
+    /// Проверка пометок в статье по id
+    async fn check_compliance(
+        &self,
+        news_id: &str,
+    ) -> Result<model::ComplianceReport, ServiceError>;
 
-    /// Добавление списка запрещенных имен
-    async fn add_warning_names(&self, new_warning_names: Vec<Record>);
+    /// Пометки для записей, упомянутых в тексте, и текст со вставленными пометками.
+    /// `html` может содержать разметку, проверяется текст без нее
+    async fn fix_text(
+        &self,
+        html: &str,
+        templates: &LabelTemplates,
+    ) -> Result<model::FixedText, ServiceError>;
+
+    /// Пометки и исправленный текст статьи по id
+    async fn fix_article(
+        &self,
+        news_id: &str,
+        templates: &LabelTemplates,
+    ) -> Result<model::FixedText, ServiceError>;
+
+    /// Изменение списка запрещенных имен.
+    ///
+    /// Применяются только отличия нового списка от текущего. Ошибка – если
+    /// перестройка индекса прервалась, тогда текущий список остается прежним
+    async fn change_warning_names(
+        &self,
+        new_warning_names: Vec<Record>,
+    ) -> Result<model::UpdateStats, ServiceError>;
+
+    /// Добавление списка запрещенных имен. Записи с уже известным id заменяются.
+    ///
+    /// Возвращает ошибки по id записей, которые не удалось добавить в индекс,
+    /// остальные записи при этом добавляются
+    async fn add_warning_names(&self, new_warning_names: Vec<Record>) -> HashMap<i64, String>;
+
+    /// Удаление имени из списка и индекса без полной перестройки
+    async fn remove_warning_name(&self, id: i64);
+
+    /// Разбор сравнения имени с записью `record_id`: формы имени, сходство,
+    /// все расстояния, пороги и итоговое решение
+    async fn explain(
+        &self,
+        entity: Entity,
+        record_id: i64,
+    ) -> Result<model::Explanation, ServiceError>;
 }
 
 pub trait SmartNameChecker {
     /// Сравнение двух имен – используется при наличии инициалов в тексте.
-    /// Например: `И.И. Иванов`
-    fn compare_names(&self, name_text: &str, name_registry: &str) -> usize;
+    /// Например: `И.И. Иванов`. Кроме итогового расстояния возвращает фамилии,
+    /// инициалы и расстояния по отдельности
+    fn compare_names(&self, name_text: &str, name_registry: &str) -> model::NameComparison;
 }
@@ -1,5 +1,9 @@
 pub mod ann;
+pub mod compliance;
+pub mod fixer;
 pub mod interface;
 pub mod model;
 pub mod new_checker;
 pub mod new_name_checker;
+pub mod registry;
+pub mod scanner;
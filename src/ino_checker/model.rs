@@ -1,8 +1,11 @@
-use crate::db::model::Record;
+use crate::db::model::{Record, RecordCategory, RecordInfo};
 use serde::Serialize;
+use std::ops::Range;
 
 pub struct RecordWithRelevance {
     pub record: Record,
+    /// Псевдоним, через который найдена запись
+    pub alias: Option<String>,
     pub similarity: f32,
 }
 
@@ -19,14 +22,49 @@ pub struct Distances {
 
 #[derive(Debug, Serialize, Clone)]
 pub struct Doc {
-    pub status: String,
+    /// id записи в базе данных, по нему запись доступна через `GET /records/{id}`
+    pub id: i64,
+    /// Вид реестра записи
+    pub status: RecordCategory,
     pub name: String,
     pub is_removed: bool,
     pub similarity: f32,
     pub distance: usize,
     pub debug_distances: Option<Distances>,
+    /// Псевдоним записи, с которым совпало имя из текста
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_alias: Option<String>,
+}
+
+/// Каким путем найдено имя
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionSource {
+    /// Имя извлечено сервисом NER и найдено в индексе
+    Ner,
+    /// Имя реестра найдено прямым поиском по тексту
+    Dictionary,
+    /// Обоими путями
+    Both,
 }
 
+/// Место имени в проверенном тексте. Смещения – в исходном тексте, вместе
+/// с HTML-разметкой, если она была: в байтах UTF-8 и в единицах UTF-16
+#[derive(Debug, Serialize, Clone)]
+pub struct Mention {
+    /// Упоминание как в тексте без разметки
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+    /// Место упоминания в тексте без разметки, в байтах
+    #[serde(skip)]
+    pub range: Range<usize>,
+}
+
+/// Имя из текста. Все упоминания одной сущности собраны в одно имя:
+/// предупреждения – по записи, принятые имена – по нормальной форме
 #[derive(Debug, Serialize)]
 pub struct WarningName {
     pub name: String,
@@ -35,12 +73,244 @@ pub struct WarningName {
     pub name_type: String,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub docs: Vec<Doc>,
+    pub source: DetectionSource,
+    /// Все упоминания в тексте, по порядку
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<Mention>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct WarningNames {
+    /// Номер снимка реестра, по которому выполнена проверка
+    pub generation: u64,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub warnings: Vec<WarningName>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub accepted_names: Vec<WarningName>,
 }
+
+/// Результат применения изменений базы данных к индексу
+#[derive(Debug, Serialize, Default)]
+pub struct UpdateStats {
+    pub inserted: usize,
+    pub removed: usize,
+    pub updated: usize,
+    /// Записи, у которых изменился только флаг `is_removed`
+    pub flag_changed: usize,
+    pub unchanged: usize,
+    /// Индекс построен заново, а не изменен по записям
+    pub rebuilt: bool,
+    /// Номер снимка реестра после обновления
+    pub generation: u64,
+    /// Записи и псевдонимы, не попавшие в индекс, например из-за другой размерности embedding
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Разбор сравнения имени с инициалами и записи реестра (`NameChecker::compare_names`)
+#[derive(Debug, Serialize, Clone)]
+pub struct NameComparison {
+    pub text_surname: String,
+    pub text_initials: String,
+    /// Фамилия записи и фамилии из псевдонимов, в виде основ
+    pub registry_surnames: Vec<String>,
+    pub registry_initials: String,
+    /// Фамилия реестра с наименьшим расстоянием
+    pub best_surname: String,
+    pub surname_distance: usize,
+    pub initials_distance: usize,
+    pub distance: usize,
+}
+
+/// Расстояния между именем из текста и именем записи, как при проверке
+#[derive(Debug, Serialize, Clone)]
+pub struct Scoring {
+    /// `unordered_levenshtein` основ нормальной формы из NER и имени реестра
+    pub norm_name_dis: usize,
+    /// `unordered_levenshtein` основ имени из текста и имени реестра
+    pub orig_name_dis: usize,
+    /// Для имени латиницей: расстояние до транслитерации имени реестра
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latin_dis: Option<usize>,
+    /// Сравнение фамилии и инициалов, только для `PER`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_comparison: Option<NameComparison>,
+    /// Учитывается ли `name_comparison`: только если в имени из текста есть инициалы
+    pub name_dis_applied: bool,
+    /// Итоговое расстояние – минимум учтенных
+    pub distance: usize,
+}
+
+impl From<&Scoring> for Distances {
+    fn from(scoring: &Scoring) -> Self {
+        Distances {
+            not_normal_dis: scoring
+                .latin_dis
+                .map_or(scoring.orig_name_dis, |d| d.min(scoring.orig_name_dis)),
+            normal_dis: scoring.norm_name_dis,
+            name_dis: scoring
+                .name_comparison
+                .as_ref()
+                .filter(|_| scoring.name_dis_applied)
+                .map(|c| c.distance),
+        }
+    }
+}
+
+/// Имя из текста в тех формах, в которых оно сравнивается с реестром
+#[derive(Debug, Serialize)]
+pub struct ExplainedEntity {
+    pub name: String,
+    pub norm_name: String,
+    pub entity_type: String,
+    /// Строка, по которой считается embedding
+    pub query_name: String,
+    /// Транслитерация кириллицей для имени латиницей
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transliterated: Option<String>,
+    /// Основы слов имени и нормальной формы
+    pub normalized_name: String,
+    pub normalized_norm_name: String,
+}
+
+/// Сравнение с одним именем записи: основным или псевдонимом
+#[derive(Debug, Serialize)]
+pub struct ExplainedCandidate {
+    pub registry_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub normalized: String,
+    /// Косинусное сходство embedding, нет для псевдонима без embedding
+    pub similarity: Option<f32>,
+    /// Входит ли это имя в ближайшие точки индекса
+    pub in_nearest: bool,
+    pub scoring: Scoring,
+    /// Проходит ли все пороги
+    pub passes: bool,
+}
+
+/// Точка индекса среди ближайших к имени из текста
+#[derive(Debug, Serialize)]
+pub struct NearestPoint {
+    pub id: i64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    pub similarity: f32,
+}
+
+/// Пороги, с которыми сравниваются сходство и расстояние
+#[derive(Debug, Serialize)]
+pub struct Thresholds {
+    /// Минимальное косинусное сходство для вида реестра записи
+    pub max_treshold: f32,
+    /// Максимальное итоговое расстояние для вида реестра записи
+    pub max_dis: usize,
+    /// Сколько ближайших точек индекса рассматривается
+    pub nearest: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Decision {
+    pub matched: bool,
+    /// Каким путем запись была бы найдена
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<DetectionSource>,
+    pub reason: String,
+}
+
+/// Полный разбор сравнения имени из текста с записью реестра
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub generation: u64,
+    pub entity: ExplainedEntity,
+    pub record: RecordInfo,
+    pub candidates: Vec<ExplainedCandidate>,
+    pub nearest: Vec<NearestPoint>,
+    /// Находит ли запись прямой поиск по словарю в строке имени
+    pub dictionary_match: bool,
+    pub thresholds: Thresholds,
+    pub decision: Decision,
+}
+
+/// Есть ли рядом с упоминанием пометка о статусе
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelStatus {
+    /// Полная формулировка рядом с именем или в сноске
+    Labeled,
+    /// Неполная формулировка рядом с именем или полная в другом месте текста
+    PartiallyLabeled,
+    Unlabeled,
+    /// Запись исключена из реестра или у вида реестра нет обязательной пометки
+    NotRequired,
+}
+
+/// Одно упоминание записи реестра в тексте и его пометка
+#[derive(Debug, Serialize)]
+pub struct MentionCompliance {
+    pub name: String,
+    /// Смещения упоминания, как в `Mention`: в исходном тексте вместе
+    /// с разметкой, в байтах UTF-8 и в единицах UTF-16
+    pub start: usize,
+    pub end: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+    /// Место упоминания в тексте без разметки, в байтах
+    #[serde(skip)]
+    pub range: Range<usize>,
+    pub context: String,
+    pub record_id: i64,
+    pub record_name: String,
+    /// Вид реестра записи
+    pub status: RecordCategory,
+    pub label_status: LabelStatus,
+    /// Найденная пометка
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Проверка пометок в тексте
+#[derive(Debug, Serialize)]
+pub struct ComplianceReport {
+    /// Номер снимка реестра, по которому выполнена проверка
+    pub generation: u64,
+    pub labeled: usize,
+    pub partially_labeled: usize,
+    pub unlabeled: usize,
+    pub mentions: Vec<MentionCompliance>,
+}
+
+/// Пометка для записи реестра, упомянутой в тексте
+#[derive(Debug, Serialize)]
+pub struct RequiredLabel {
+    pub record_id: i64,
+    pub record_name: String,
+    /// Вид реестра записи
+    pub status: RecordCategory,
+    /// Первое упоминание записи и его смещения, как в `Mention`
+    pub mention: String,
+    pub start: usize,
+    pub end: usize,
+    pub start_utf16: usize,
+    pub end_utf16: usize,
+    /// Пометка у первого упоминания до исправления
+    pub label_status: LabelStatus,
+    /// Текст пометки. Нет для исключенных записей и своих видов реестра
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Пометка вставлена в текст и в HTML
+    pub inserted: bool,
+}
+
+/// Пометки для текста и исправленный текст
+#[derive(Debug, Serialize)]
+pub struct FixedText {
+    /// Номер снимка реестра, по которому выполнена проверка
+    pub generation: u64,
+    pub labels: Vec<RequiredLabel>,
+    /// Текст без разметки со вставленными пометками
+    pub text: String,
+    /// Исходный HTML со вставленными пометками
+    pub html: String,
+}
@@ -1,69 +1,185 @@
-use crate::db::model::Record;
+use crate::db::model::{Record, RecordCategory, RecordInfo};
 use crate::embedding::interface::Embedding;
-use crate::ino_checker::ann::ANNIndex;
+use crate::embedding::model::Embeddings;
+use crate::error::model::ServiceError;
+use crate::import::kinds::{min_treshold, registry_kind};
+use crate::ino_checker::ann::content_hash;
+use crate::ino_checker::compliance::ComplianceChecker;
+use crate::ino_checker::fixer::{self, LabelTemplates};
 use crate::ino_checker::interface::{BasicChecker, SmartNameChecker};
 use crate::ino_checker::model::{self, WarningName};
+use crate::ino_checker::registry::{IndexPersister, Registry, check_dimensions};
+use crate::ino_checker::scanner::{PatternKind, mention_ranges, normalize};
 use crate::ner::interface::Entities;
 use crate::ner::model::Entity;
-use crate::rv::get::get_text;
-use crate::utils::funcs::keep_russian_and_dot;
+use crate::resilience::upstream::Upstream;
+use crate::rv::get::{StrippedHtml, get_html};
 use crate::utils::funcs::{cosine_similarity, unordered_levenshtein};
-use std::collections::HashMap;
-use tokio::sync::Mutex;
+use crate::utils::funcs::{keep_russian_and_dot, text_context};
+use crate::utils::morphology::normalize_name;
+use crate::utils::translit::{Scheme, to_cyrillic, to_latin};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
 
 use futures::future::join_all;
-type TaskResult = Result<(Option<model::WarningName>, Option<model::WarningName>), anyhow::Error>;
+type TaskResult = Result<(Option<model::WarningName>, Option<model::WarningName>), ServiceError>;
 
-const MAX_DIS: usize = 7;
-const MAX_TRESHOLD: f32 = 0.61;
+/// Латинское имя короче не ищется в транслитерации реестра
+const MIN_TRANSLIT_CHARS: usize = 4;
+/// Сколько ближайших точек индекса рассматривается для каждого имени
+const NEAREST: usize = 5;
+
+/// Пороги отбора записей из индекса
+#[derive(Clone, Copy)]
+enum Limits {
+    /// Пороги вида реестра каждой записи
+    PerKind,
+    /// Одни пороги для всех записей: сходство и расстояние
+    Fixed(f32, usize),
+}
+
+impl Limits {
+    /// Порог сходства для первичного отбора из индекса
+    fn min_treshold(self) -> f32 {
+        match self {
+            Limits::PerKind => min_treshold(),
+            Limits::Fixed(treshold, _) => treshold,
+        }
+    }
+
+    fn passes(self, category: &RecordCategory, similarity: f32, distance: usize) -> bool {
+        let (treshold, max_distance) = match self {
+            Limits::PerKind => {
+                let kind = registry_kind(category);
+                (kind.max_treshold, kind.max_dis)
+            }
+            Limits::Fixed(treshold, max_distance) => (treshold, max_distance),
+        };
+        similarity >= treshold && distance <= max_distance
+    }
+}
+
+/// Имя из текста в формах, с которыми сравнивается реестр: имя латиницей
+/// сравнивается в транслитерации кириллицей
+struct TextForms {
+    name: String,
+    norm_name: String,
+    is_latin: bool,
+}
+
+impl TextForms {
+    fn new(entity: &Entity) -> Self {
+        let is_latin = keep_russian_and_dot(&entity.name).is_empty();
+        if is_latin {
+            TextForms {
+                name: to_cyrillic(&entity.name),
+                norm_name: to_cyrillic(&entity.norm_name),
+                is_latin,
+            }
+        } else {
+            TextForms {
+                name: entity.name.clone(),
+                norm_name: entity.norm_name.clone(),
+                is_latin,
+            }
+        }
+    }
+}
 
 pub struct WarningNamesChecker<T: Embedding, S: SmartNameChecker, E: Entities> {
-    warning_names: Mutex<Vec<Record>>,
+    /// Список имен, индекс и поиск по латинице из одного снимка базы данных.
+    /// Проверки берут снимок и сразу отпускают блокировку, поэтому изменение
+    /// реестра не ждет проверок, которые обращаются к внешним сервисам
+    registry: RwLock<Arc<Registry>>,
     vectorizer: T,
     name_checker: S,
     entities: E,
-    index: Mutex<ANNIndex>,
+    compliance: ComplianceChecker,
+    /// Ограничитель, повторы и предохранитель для запросов текста статьи
+    rv: Upstream,
+    /// Не дает двум изменениям реестра (`/update`, `/add`, удаление) строить
+    /// новые снимки одновременно и терять изменения друг друга
+    update_lock: Mutex<()>,
+    /// Сохраняет индекс после изменений, не задерживая их
+    persister: IndexPersister,
 }
 
 // basic public functions
 impl<T: Embedding, S: SmartNameChecker, E: Entities> WarningNamesChecker<T, S, E> {
-    pub fn new(warning_names: Vec<Record>, vectorizer: T, name_checker: S, entities: E) -> Self {
-        let mut index = ANNIndex::new("warning-names", 256);
-        println!("Adding name to index");
-        let mut i = 1;
-        for name in warning_names.clone() {
-            println!("Adding name {i}/{}", warning_names.len());
-            i += 1;
-            index.add(name);
+    /// `index_path` – файл для сохранения индекса между запусками.
+    /// Размерность индекса берется из `vectorizer`
+    pub fn new(
+        warning_names: Vec<Record>,
+        vectorizer: T,
+        name_checker: S,
+        entities: E,
+        index_path: Option<String>,
+        rv: Upstream,
+    ) -> Self {
+        let (registry, errors) =
+            Registry::build(warning_names, vectorizer.dimension(), 1, index_path);
+        for e in errors {
+            eprintln!("{e}");
+        }
+        // При запуске других сохранений еще нет, поэтому индекс сохраняется сразу
+        if registry.needs_persist() {
+            registry.persist();
         }
 
         WarningNamesChecker {
-            warning_names: Mutex::new(warning_names),
+            registry: RwLock::new(Arc::new(registry)),
             vectorizer,
             name_checker,
             entities,
-            index: Mutex::new(index),
+            compliance: ComplianceChecker::new(),
+            rv,
+            update_lock: Mutex::new(()),
+            persister: IndexPersister::default(),
         }
     }
 }
 
 // basic non-public functions
 impl<T: Embedding, S: SmartNameChecker, E: Entities> WarningNamesChecker<T, S, E> {
-    async fn get_must_relevant(
+    /// Текущий снимок реестра
+    async fn snapshot(&self) -> Arc<Registry> {
+        self.registry.read().await.clone()
+    }
+
+    /// Изменение реестра: `change` применяется к копии текущего снимка, копия
+    /// получает следующий номер и подменяет снимок. Вызывается под `update_lock`
+    async fn modify_registry<R>(&self, change: impl FnOnce(&mut Registry) -> R) -> R {
+        let mut registry = Registry::clone(&*self.snapshot().await);
+        let result = change(&mut registry);
+        registry.generation += 1;
+        registry.rebuild_scanner();
+
+        let registry = Arc::new(registry);
+        *self.registry.write().await = registry.clone();
+        self.persister.schedule(registry);
+        result
+    }
+
+    fn get_must_relevant(
         &self,
+        registry: &Registry,
         name: &[f32],
         number: usize,
         treshold: f32,
-    ) -> Vec<model::RecordWithRelevance> {
+    ) -> Result<Vec<model::RecordWithRelevance>, anyhow::Error> {
         let mut filtered_with_relevance: Vec<model::RecordWithRelevance> = Vec::new();
 
-        let warning_names = self.index.lock().await.search(name, number);
+        let warning_names = registry.index.search(name, number)?;
 
         for agent in warning_names {
             let sim = cosine_similarity(name, &agent.embedding);
             if sim >= treshold {
                 let op = model::RecordWithRelevance {
-                    record: agent.clone(),
+                    record: agent.record,
+                    alias: agent.alias,
                     similarity: sim,
                 };
                 filtered_with_relevance.push(op);
@@ -75,64 +191,53 @@ impl<T: Embedding, S: SmartNameChecker, E: Entities> WarningNamesChecker<T, S, E
                 .unwrap_or(std::cmp::Ordering::Equal)
         });
         filtered_with_relevance.truncate(number);
-        filtered_with_relevance
+        Ok(filtered_with_relevance)
     }
 
     async fn get_most_relevant_names(
         &self,
-        treshold: f32,
-        max_distance: usize,
+        registry: &Registry,
+        embeddings: &Embeddings,
+        limits: Limits,
         entity: &Entity,
-    ) -> Result<Option<model::WarningName>, anyhow::Error> {
-        let name = keep_russian_and_dot(&entity.name);
+    ) -> Result<Option<model::WarningName>, ServiceError> {
+        let name = query_name(&entity.name);
         if name.is_empty() {
             return Ok(None);
         }
+        let forms = TextForms::new(entity);
 
-        let embedding = self.fetch_entity_embedding_with_retry(&name, 3).await?;
+        // Embedding запрошен заранее для всех имен статьи. Повторы при сбоях уже
+        // выполнены внутри `vectorizer`, поэтому ошибка возвращается как есть
+        let embedding = match embeddings.get(&name) {
+            Some(Ok(e)) => e.clone(),
+            Some(Err(e)) => return Err(e.clone()),
+            None => self.vectorizer.get_embedding(&name).await?.into_result()?,
+        };
 
-        let most_relevant = self.get_must_relevant(&embedding, 5, treshold).await;
+        let most_relevant =
+            self.get_must_relevant(registry, &embedding, NEAREST, limits.min_treshold())?;
         if most_relevant.is_empty() {
             return Ok(None);
         }
         let mut docs: Vec<model::Doc> = Vec::new();
         for ag in most_relevant {
-            let mut dis = unordered_levenshtein(
-                &entity.norm_name.to_lowercase(),
-                &ag.record.name.to_lowercase(),
-            );
-            let mut dis2 =
-                unordered_levenshtein(&entity.name.to_lowercase(), &ag.record.name.to_lowercase());
-
-            let mut distances = model::Distances {
-                not_normal_dis: dis2,
-                normal_dis: dis,
-                name_dis: None,
-            };
-
-            if entity.entity_type == "PER" {
-                let name_dis = self.name_checker.compare_names(
-                    &entity.name.to_lowercase(),
-                    &ag.record.name.to_ascii_lowercase(),
-                );
-
-                if entity.name.contains(".") {
-                    distances.name_dis = Some(name_dis);
-                    dis = std::cmp::min(dis, name_dis);
-                    dis2 = std::cmp::min(dis2, name_dis)
-                }
-            }
+            // Если запись найдена по псевдониму, расстояние считаем до псевдонима
+            let registry_name = ag.alias.as_deref().unwrap_or(&ag.record.name);
+            let scoring = self.score(entity, &forms, registry_name, &ag.record.name);
+            let dis = scoring.distance;
+            let distances = model::Distances::from(&scoring);
 
-            dis = std::cmp::min(dis, dis2);
-
-            if dis <= max_distance {
+            if limits.passes(&ag.record.record_type, ag.similarity, dis) {
                 let doc = model::Doc {
+                    id: ag.record.id,
                     name: ag.record.name.clone(),
                     is_removed: ag.record.is_removed,
                     status: ag.record.record_type.clone(),
                     similarity: ag.similarity,
                     distance: dis,
                     debug_distances: Some(distances.clone()),
+                    matched_alias: ag.alias.clone(),
                 };
                 docs.push(doc);
             }
@@ -141,164 +246,181 @@ impl<T: Embedding, S: SmartNameChecker, E: Entities> WarningNamesChecker<T, S, E
         if docs.is_empty() {
             Ok(None)
         } else {
-            let status_docs = self.process_docs(docs);
+            let status_docs = process_docs(docs);
             let ino = model::WarningName {
                 name: entity.name.clone(),
                 normal_name: entity.norm_name.clone(),
                 context: entity.context.clone(),
                 name_type: entity.entity_type.clone(),
                 docs: status_docs,
+                source: model::DetectionSource::Ner,
+                mentions: Vec::new(),
             };
             Ok(Some(ino))
         }
     }
 
-    fn process_docs(&self, docs: Vec<model::Doc>) -> Vec<model::Doc> {
-        let mut grouped: HashMap<String, Vec<model::Doc>> = HashMap::new();
-
-        // 1. Разбиваем по status
-        for doc in docs {
-            grouped.entry(doc.status.clone()).or_default().push(doc);
-        }
-
-        let mut result = Vec::new();
+    /// Расстояния между именем из текста и именем записи (основным или псевдонимом).
+    ///
+    /// Сравниваются основы слов, чтобы падеж и род имени не увеличивали расстояние.
+    /// Для имени латиницей считается еще и расстояние до транслитерации имени реестра
+    /// (меньшее по системам BGN/PCGN и ГОСТ),
+    /// для персоны с инициалами – сравнение фамилии и инициалов
+    fn score(
+        &self,
+        entity: &Entity,
+        forms: &TextForms,
+        registry_name: &str,
+        record_name: &str,
+    ) -> model::Scoring {
+        let latin_dis = forms.is_latin.then(|| {
+            let name = entity.name.to_lowercase();
+            Scheme::ALL
+                .into_iter()
+                .map(|scheme| unordered_levenshtein(&name, &to_latin(registry_name, scheme)))
+                .min()
+                .unwrap_or_default()
+        });
+        let registry_name = normalize_name(registry_name);
+        let norm_name_dis =
+            unordered_levenshtein(&normalize_name(&forms.norm_name), &registry_name);
+        let orig_name_dis = unordered_levenshtein(&normalize_name(&forms.name), &registry_name);
 
-        // 2. Сортируем каждую группу и 3. Берем первый элемент
-        for (_, mut group) in grouped {
-            group.sort_by_key(|d| d.distance);
-            if let Some(first) = group.into_iter().next() {
-                result.push(first);
-            }
-        }
+        let name_comparison = (entity.entity_type == "PER").then(|| {
+            self.name_checker.compare_names(
+                &forms.name.to_lowercase(),
+                &record_name.to_ascii_lowercase(),
+            )
+        });
+        let name_dis_applied = name_comparison.is_some() && entity.name.contains('.');
 
-        result
-    }
+        let distance = [
+            Some(norm_name_dis),
+            Some(orig_name_dis),
+            latin_dis,
+            name_comparison
+                .as_ref()
+                .filter(|_| name_dis_applied)
+                .map(|c| c.distance),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(usize::MAX);
 
-    async fn fetch_entity_embedding_with_retry(
-        &self,
-        name: &str,
-        max_retry: u8,
-    ) -> Result<Vec<f32>, anyhow::Error> {
-        use tokio::time::{Duration, sleep};
-
-        let mut last_error: Option<anyhow::Error> = None;
-
-        for _ in 0..max_retry {
-            match self.vectorizer.get_embedding(name).await {
-                Ok(e) => {
-                    if let Some(e) = e.error {
-                        last_error = Some(anyhow::anyhow!("{e}"));
-                    } else if let Some(e) = e.embedding {
-                        return Ok(e);
-                    } else {
-                        last_error = Some(anyhow::anyhow!("Unknown embedding error"));
-                    }
-                }
-                Err(e) => {
-                    last_error = Some(anyhow::anyhow!("{e}"));
-                    sleep(Duration::from_secs(1)).await;
-                }
-            }
+        model::Scoring {
+            norm_name_dis,
+            orig_name_dis,
+            latin_dis,
+            name_comparison,
+            name_dis_applied,
+            distance,
         }
-
-        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("Unknown embedding error")))
     }
 
-    async fn get_entities_list_with_retry(
-        &self,
-        text: &str,
-        max_retry: u8,
-    ) -> Result<Vec<Entity>, anyhow::Error> {
-        //let embeddibg_url = env::var("ENTITIES_URL").ok().unwrap();
-        use tokio::time::{Duration, sleep};
-        let mut last_error: Option<anyhow::Error> = None;
-        for _ in 0..max_retry {
-            match self.entities.get_entities(text).await {
-                Ok(e) => return Ok(e.entities),
-                Err(e) => {
-                    last_error = Some(e);
-                    sleep(Duration::from_millis(30)).await;
-                }
-            }
+    /// Поиск латинского имени, которое не нашлось по индексу: в написании реестра,
+    /// в псевдонимах и в транслитерации латиницей. Имя сравнивается целыми словами
+    /// (см. `latin_similarity()`) и проходит те же пороги вида реестра, что и поиск по индексу
+    fn check_english_name(&self, registry: &Registry, entity: Entity) -> Option<WarningName> {
+        let test_name = entity.name.to_lowercase();
+        let words = latin_words(&test_name);
+        if words.is_empty() {
+            return None;
         }
-        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("Unknown embedding error")))
-    }
-
-    async fn check_english_name(&self, entity: Entity) -> Option<WarningName> {
-        let test_name = entity.name.clone().to_lowercase();
+        let forms = TextForms::new(&entity);
         let mut res = WarningName {
             name: entity.name.clone(),
             normal_name: entity.norm_name.clone(),
             name_type: entity.entity_type.clone(),
             context: entity.context.clone(),
             docs: Vec::new(),
+            source: model::DetectionSource::Ner,
+            mentions: Vec::new(),
         };
-        for warning_name in self.warning_names.lock().await.clone() {
-            let u_name = warning_name.name.to_lowercase();
-            if u_name.contains(&test_name) {
-                let doc = model::Doc {
-                    status: warning_name.record_type.clone(),
-                    similarity: 1.0,
-                    distance: 0,
-                    is_removed: warning_name.is_removed,
-                    name: warning_name.name.clone(),
-                    debug_distances: None,
-                };
-                res.docs.push(doc);
+        // Короткие имена в транслитерации дают слишком много случайных совпадений
+        let translit = test_name.chars().count() >= MIN_TRANSLIT_CHARS;
+
+        for (warning_name, lowercase) in registry.names() {
+            // Написания записи: имя, псевдонимы и их транслитерация. Для каждого
+            // запоминается номер псевдонима, `None` – основное имя
+            let spellings = std::iter::once((&lowercase.name, None))
+                .chain(
+                    lowercase
+                        .aliases
+                        .iter()
+                        .enumerate()
+                        .map(|(i, a)| (a, Some(i))),
+                )
+                .chain(
+                    lowercase
+                        .latin
+                        .iter()
+                        .filter(|_| translit)
+                        .map(|(l, alias)| (l, *alias)),
+                );
+            let best = spellings
+                .filter_map(|(form, alias)| Some((latin_similarity(&words, form)?, alias)))
+                .max_by(|a, b| a.0.total_cmp(&b.0));
+            let Some((similarity, alias)) = best else {
+                continue;
+            };
+
+            let matched_alias = alias.and_then(|i| warning_name.aliases.get(i));
+            let registry_name = matched_alias.map_or(&warning_name.name, |a| &a.name);
+            let scoring = self.score(&entity, &forms, registry_name, &warning_name.name);
+            if !Limits::PerKind.passes(&warning_name.record_type, similarity, scoring.distance) {
+                continue;
             }
+            res.docs.push(model::Doc {
+                id: warning_name.id,
+                status: warning_name.record_type.clone(),
+                similarity,
+                distance: scoring.distance,
+                is_removed: warning_name.is_removed,
+                name: warning_name.name.clone(),
+                debug_distances: Some(model::Distances::from(&scoring)),
+                matched_alias: matched_alias.map(|a| a.name.clone()),
+            });
         }
         if !res.docs.is_empty() {
-            let new_docs = self.process_docs(res.docs.clone());
+            let new_docs = process_docs(res.docs.clone());
             res.docs = new_docs;
             return Some(res);
         }
         None
     }
-}
 
-// trait implementation
-impl<T: Embedding, S: SmartNameChecker, E: Entities> BasicChecker for WarningNamesChecker<T, S, E> {
-    async fn change_warning_names(&self, new_warning_names: Vec<Record>) {
-        let mut guard = self.index.lock().await;
-        let mut new_index = ANNIndex::new("warning_names", 256);
-        println!("Adding name to index");
-        let mut i = 1;
-        let len = new_warning_names.len();
-        for name in new_warning_names.clone() {
-            println!("Adding name {i}/{len}");
-            i += 1;
-            new_index.add(name);
-        }
-        *guard = new_index;
-    }
-
-    async fn add_warning_names(&self, new_warning_names: Vec<Record>) {
-        let mut w_guard = self.warning_names.lock().await;
-        w_guard.extend(new_warning_names.clone());
-        let mut idx_guard = self.index.lock().await;
-        println!("Adding name to index");
-        let mut i = 1;
-        let len = new_warning_names.len();
-        for name in new_warning_names {
-            println!("Adding name {i}/{len}");
-            i += 1;
-            idx_guard.add(name);
-        }
-    }
-
-    async fn get_inos_from_text(
+    /// Имена из текста `source` и их упоминания. Проверяется текст без разметки
+    /// `stripped`, смещения упоминаний – в исходном тексте
+    async fn find_names(
         &self,
-        text: &str,
+        source: &str,
+        stripped: &StrippedHtml,
         need_full_data: bool,
-    ) -> Result<model::WarningNames, anyhow::Error> {
-        let entities = self.get_entities_list_with_retry(text, 3).await?;
+    ) -> Result<model::WarningNames, ServiceError> {
+        let plain = stripped.text.as_str();
+        let entities = self.entities.get_entities(plain).await?.entities;
+
+        // Все имена статьи векторизуются одним набором, повторы запрашиваются один раз
+        let names: Vec<String> = entities
+            .iter()
+            .filter(|e| e.entity_type == "PER" || e.entity_type == "ORG")
+            .map(|e| query_name(&e.name))
+            .filter(|name| !name.is_empty())
+            .collect();
+        let embeddings = self.vectorizer.get_embeddings(&names).await;
+        let embeddings = &embeddings;
+
+        // Вся проверка текста идет по одному снимку реестра
+        let registry = self.snapshot().await;
+        let registry = &*registry;
 
         // Собираем задачи
         let tasks = entities.into_iter().map(|entity| {
             let this = self;
             async move {
                 if entity.entity_type != "PER" && entity.entity_type != "ORG" {
-                    return Ok::<_, anyhow::Error>((
+                    return Ok::<_, ServiceError>((
                         None,
                         Some(model::WarningName {
                             name: entity.name,
@@ -306,12 +428,14 @@ impl<T: Embedding, S: SmartNameChecker, E: Entities> BasicChecker for WarningNam
                             context: entity.context,
                             name_type: entity.entity_type,
                             docs: Vec::new(),
+                            source: model::DetectionSource::Ner,
+                            mentions: Vec::new(),
                         }),
                     ));
                 }
 
                 let processed = this
-                    .get_most_relevant_names(MAX_TRESHOLD, MAX_DIS, &entity)
+                    .get_most_relevant_names(registry, embeddings, Limits::PerKind, &entity)
                     .await?;
 
                 if let Some(ino) = processed {
@@ -319,7 +443,7 @@ impl<T: Embedding, S: SmartNameChecker, E: Entities> BasicChecker for WarningNam
                 }
 
                 if keep_russian_and_dot(&entity.name).is_empty() {
-                    if let Some(e) = this.check_english_name(entity.clone()).await {
+                    if let Some(e) = this.check_english_name(registry, entity.clone()) {
                         return Ok((Some(e), None));
                     } else if need_full_data {
                         return Ok((
@@ -330,11 +454,20 @@ impl<T: Embedding, S: SmartNameChecker, E: Entities> BasicChecker for WarningNam
                                 context: entity.context.clone(),
                                 name_type: entity.entity_type.clone(),
                                 docs: Vec::new(),
+                                source: model::DetectionSource::Ner,
+                                mentions: Vec::new(),
                             }),
                         ));
                     }
                 } else if need_full_data {
-                    let most_relevant = this.get_most_relevant_names(0.0, 100, &entity).await?;
+                    let most_relevant = this
+                        .get_most_relevant_names(
+                            registry,
+                            embeddings,
+                            Limits::Fixed(0.0, 100),
+                            &entity,
+                        )
+                        .await?;
                     if let Some(e) = most_relevant {
                         return Ok((None, Some(e)));
                     }
@@ -348,6 +481,8 @@ impl<T: Embedding, S: SmartNameChecker, E: Entities> BasicChecker for WarningNam
                         context: entity.context,
                         name_type: entity.entity_type,
                         docs: Vec::new(),
+                        source: model::DetectionSource::Ner,
+                        mentions: Vec::new(),
                     }),
                 ))
             }
@@ -369,18 +504,701 @@ impl<T: Embedding, S: SmartNameChecker, E: Entities> BasicChecker for WarningNam
             }
         }
 
+        // Имена реестра, которые NER не извлек, находятся прямым поиском по тексту
+        let hits = self.merge_dictionary_hits(registry, plain, &mut inos, &mut accepted_names);
+
+        let same_record = |a: &WarningName, b: &WarningName| {
+            a.docs.first().map(|d| d.id) == b.docs.first().map(|d| d.id)
+        };
+        let same_name = |a: &WarningName, b: &WarningName| {
+            a.name_type == b.name_type
+                && normalize(a.normal_name.trim()) == normalize(b.normal_name.trim())
+        };
         Ok(model::WarningNames {
-            warnings: inos,
-            accepted_names,
+            generation: registry.generation,
+            warnings: group_mentions(source, stripped, inos, hits, same_record),
+            accepted_names: group_mentions(source, stripped, accepted_names, Vec::new(), same_name),
+        })
+    }
+
+    /// Проверка пометок в тексте `source`, `stripped` – он же без разметки
+    async fn compliance_report(
+        &self,
+        source: &str,
+        stripped: &StrippedHtml,
+    ) -> Result<model::ComplianceReport, ServiceError> {
+        let inos = self.find_names(source, stripped, false).await?;
+        let mentions = self.compliance.check(&stripped.text, &inos.warnings);
+        let count = |status: model::LabelStatus| {
+            mentions.iter().filter(|m| m.label_status == status).count()
+        };
+
+        Ok(model::ComplianceReport {
+            generation: inos.generation,
+            labeled: count(model::LabelStatus::Labeled),
+            partially_labeled: count(model::LabelStatus::PartiallyLabeled),
+            unlabeled: count(model::LabelStatus::Unlabeled),
+            mentions,
         })
     }
 
+    /// Слияние совпадений прямого поиска по тексту с именами, найденными через NER.
+    ///
+    /// Совпадение относится к имени из NER, если одно написание содержит другое:
+    /// к предупреждению добавляется документ, имя из `accepted_names` переносится
+    /// в предупреждения. Остальные совпадения становятся новыми предупреждениями.
+    /// Возвращает места совпадений в тексте с номером предупреждения в `inos`
+    fn merge_dictionary_hits(
+        &self,
+        registry: &Registry,
+        text: &str,
+        inos: &mut Vec<WarningName>,
+        accepted_names: &mut Vec<WarningName>,
+    ) -> Vec<(usize, Range<usize>)> {
+        let mut placed = Vec::new();
+        for hit in registry.scanner.scan(text) {
+            let Some(record) = registry.record(hit.record_id) else {
+                continue;
+            };
+            let matched = &text[hit.range.clone()];
+            let matched_lower = matched.to_lowercase();
+            let same_mention = |i: &WarningName| {
+                let name = i.name.to_lowercase();
+                name.contains(&matched_lower) || matched_lower.contains(&name)
+            };
+
+            let matched_alias = match hit.kind {
+                PatternKind::Alias(i) => record.aliases.get(i).map(|a| a.name.clone()),
+                PatternKind::Name | PatternKind::Surname => None,
+            };
+            let registry_name = matched_alias.as_deref().unwrap_or(&record.name);
+            let doc = model::Doc {
+                id: record.id,
+                status: record.record_type.clone(),
+                name: record.name.clone(),
+                is_removed: record.is_removed,
+                similarity: 1.0,
+                distance: unordered_levenshtein(
+                    &normalize_name(matched),
+                    &normalize_name(registry_name),
+                ),
+                debug_distances: None,
+                matched_alias,
+            };
+
+            if let Some(pos) = inos.iter().position(same_mention) {
+                let ino = &mut inos[pos];
+                if !ino.docs.iter().any(|d| d.id == record.id) {
+                    ino.docs.push(doc);
+                }
+                if ino.source == model::DetectionSource::Ner {
+                    ino.source = model::DetectionSource::Both;
+                }
+                placed.push((pos, hit.range));
+                continue;
+            }
+
+            if let Some(pos) = accepted_names.iter().position(same_mention) {
+                let accepted = accepted_names.remove(pos);
+                inos.push(WarningName {
+                    docs: vec![doc],
+                    source: model::DetectionSource::Both,
+                    ..accepted
+                });
+                placed.push((inos.len() - 1, hit.range));
+                continue;
+            }
+
+            // Другое упоминание записи, уже найденной через NER
+            if let Some(pos) = inos
+                .iter()
+                .position(|i| i.docs.iter().any(|d| d.id == record.id))
+            {
+                let ino = &mut inos[pos];
+                if ino.source == model::DetectionSource::Ner {
+                    ino.source = model::DetectionSource::Both;
+                }
+                placed.push((pos, hit.range));
+                continue;
+            }
+
+            inos.push(WarningName {
+                name: matched.to_string(),
+                normal_name: matched.to_string(),
+                context: text_context(text, hit.range.clone()),
+                name_type: hit.entity_type.to_string(),
+                docs: vec![doc],
+                source: model::DetectionSource::Dictionary,
+                mentions: Vec::new(),
+            });
+            placed.push((inos.len() - 1, hit.range));
+        }
+        placed
+    }
+}
+
+// trait implementation
+impl<T: Embedding, S: SmartNameChecker, E: Entities> BasicChecker for WarningNamesChecker<T, S, E> {
+    async fn change_warning_names(
+        &self,
+        new_warning_names: Vec<Record>,
+    ) -> Result<model::UpdateStats, ServiceError> {
+        let _update = self.update_lock.lock().await;
+        let (current, generation, dim, index_path) = {
+            let registry = self.snapshot().await;
+            (
+                registry.fingerprints(),
+                registry.generation,
+                registry.dim(),
+                registry.index_path(),
+            )
+        };
+
+        // Записи с embedding другой размерности сравниваются как отсутствующие в базе
+        let (new_warning_names, errors) = check_dimensions(new_warning_names, dim);
+        let mut stats = model::UpdateStats {
+            errors,
+            ..Default::default()
+        };
+        let mut changed = Vec::new();
+        let mut flags = Vec::new();
+        let new_ids: HashSet<i64> = new_warning_names.iter().map(|r| r.id).collect();
+
+        for rec in &new_warning_names {
+            match current.get(&rec.id) {
+                None => {
+                    stats.inserted += 1;
+                    changed.push(rec.clone());
+                }
+                Some((hash, _)) if *hash != content_hash(rec) => {
+                    stats.updated += 1;
+                    changed.push(rec.clone());
+                }
+                Some((_, is_removed)) if *is_removed != rec.is_removed => {
+                    stats.flag_changed += 1;
+                    flags.push((rec.id, rec.is_removed));
+                }
+                Some(_) => stats.unchanged += 1,
+            }
+        }
+        let removed: Vec<i64> = current
+            .keys()
+            .filter(|id| !new_ids.contains(id))
+            .copied()
+            .collect();
+        stats.removed = removed.len();
+
+        if changed.is_empty() && flags.is_empty() && removed.is_empty() {
+            stats.generation = generation;
+            return Ok(stats);
+        }
+        stats.generation = generation + 1;
+
+        if (changed.len() + removed.len()) * 2 > new_warning_names.len() {
+            // Изменилась большая часть реестра: строим новый снимок без блокировки
+            // и подменяем его целиком, проверки в это время работают со старым
+            println!("Rebuilding index with {} names", new_warning_names.len());
+            let (new_registry, errors) = tokio::task::spawn_blocking(move || {
+                Registry::build(new_warning_names, dim, generation + 1, index_path)
+            })
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Index build failed: {e}")))?;
+            let new_registry = Arc::new(new_registry);
+            *self.registry.write().await = new_registry.clone();
+            if new_registry.needs_persist() {
+                self.persister.schedule(new_registry);
+            }
+            stats.errors.extend(errors);
+            stats.rebuilt = true;
+        } else {
+            let errors = self
+                .modify_registry(|registry| {
+                    for id in removed {
+                        registry.remove(id);
+                    }
+                    for (id, is_removed) in flags {
+                        registry.set_removed(id, is_removed);
+                    }
+                    changed
+                        .into_iter()
+                        .filter_map(|rec| registry.upsert(rec).err())
+                        .map(|e| format!("{e}"))
+                        .collect::<Vec<_>>()
+                })
+                .await;
+            stats.errors.extend(errors);
+        }
+
+        Ok(stats)
+    }
+
+    async fn add_warning_names(&self, new_warning_names: Vec<Record>) -> HashMap<i64, String> {
+        let _update = self.update_lock.lock().await;
+        println!("Adding {} names to index", new_warning_names.len());
+        self.modify_registry(|registry| {
+            new_warning_names
+                .into_iter()
+                .filter_map(|rec| {
+                    let id = rec.id;
+                    registry.upsert(rec).err().map(|e| (id, format!("{e}")))
+                })
+                .collect()
+        })
+        .await
+    }
+
+    async fn remove_warning_name(&self, id: i64) {
+        let _update = self.update_lock.lock().await;
+        self.modify_registry(|registry| registry.remove(id)).await;
+    }
+
+    async fn explain(
+        &self,
+        entity: Entity,
+        record_id: i64,
+    ) -> Result<model::Explanation, ServiceError> {
+        let query = query_name(&entity.name);
+        if query.is_empty() {
+            return Err(ServiceError::InvalidInput(format!(
+                "В имени {} нет букв для поиска",
+                entity.name
+            )));
+        }
+        let embedding = self.vectorizer.get_embedding(&query).await?.into_result()?;
+
+        let registry = self.snapshot().await;
+        let record = registry
+            .record(record_id)
+            .ok_or_else(|| ServiceError::NotFound(format!("Запись {record_id} не найдена")))?;
+        let kind = registry_kind(&record.record_type);
+        let thresholds = model::Thresholds {
+            max_treshold: kind.max_treshold,
+            max_dis: kind.max_dis,
+            nearest: NEAREST,
+        };
+
+        let nearest: Vec<model::NearestPoint> = registry
+            .index
+            .search(&embedding, NEAREST)?
+            .into_iter()
+            .map(|m| model::NearestPoint {
+                id: m.record.id,
+                name: m.record.name,
+                alias: m.alias,
+                similarity: cosine_similarity(&embedding, &m.embedding),
+            })
+            .collect();
+
+        // Основное имя и все псевдонимы записи – как отдельные точки индекса
+        let forms = TextForms::new(&entity);
+        let points = std::iter::once((None, record.embedding.as_ref())).chain(
+            record
+                .aliases
+                .iter()
+                .map(|a| (Some(a.name.clone()), a.embedding.as_ref())),
+        );
+        let candidates: Vec<model::ExplainedCandidate> = points
+            .map(|(alias, point)| {
+                let registry_name = alias.as_deref().unwrap_or(&record.name);
+                let similarity = point.map(|e| cosine_similarity(&embedding, e));
+                let in_nearest = nearest
+                    .iter()
+                    .any(|p| p.id == record.id && p.alias == alias);
+                let scoring = self.score(&entity, &forms, registry_name, &record.name);
+                let passes = in_nearest
+                    && similarity.is_some_and(|s| s >= thresholds.max_treshold)
+                    && scoring.distance <= thresholds.max_dis;
+                model::ExplainedCandidate {
+                    registry_name: registry_name.to_string(),
+                    normalized: normalize_name(registry_name),
+                    alias,
+                    similarity,
+                    in_nearest,
+                    scoring,
+                    passes,
+                }
+            })
+            .collect();
+
+        let dictionary_match = registry
+            .scanner
+            .scan(&entity.name)
+            .iter()
+            .any(|h| h.record_id == record.id);
+        let decision = decide(record, &candidates, &thresholds, dictionary_match);
+
+        Ok(model::Explanation {
+            generation: registry.generation,
+            entity: model::ExplainedEntity {
+                query_name: query,
+                transliterated: forms.is_latin.then(|| forms.name.clone()),
+                normalized_name: normalize_name(&forms.name),
+                normalized_norm_name: normalize_name(&forms.norm_name),
+                name: entity.name,
+                norm_name: entity.norm_name,
+                entity_type: entity.entity_type,
+            },
+            record: RecordInfo::from(record),
+            candidates,
+            nearest,
+            dictionary_match,
+            thresholds,
+            decision,
+        })
+    }
+
+    async fn get_inos_from_text(
+        &self,
+        text: &str,
+        need_full_data: bool,
+    ) -> Result<model::WarningNames, ServiceError> {
+        self.find_names(text, &StrippedHtml::new(text), need_full_data)
+            .await
+    }
+
     async fn get_inos(
         &self,
         news_id: &str,
         need_full_data: bool,
-    ) -> Result<model::WarningNames, anyhow::Error> {
-        let text = get_text(news_id).await?;
-        self.get_inos_from_text(&text, need_full_data).await
+    ) -> Result<model::WarningNames, ServiceError> {
+        let html = get_html(news_id, &self.rv).await?;
+        self.get_inos_from_text(&html, need_full_data).await
+    }
+
+    async fn check_compliance_from_text(
+        &self,
+        text: &str,
+    ) -> Result<model::ComplianceReport, ServiceError> {
+        self.compliance_report(text, &StrippedHtml::new(text)).await
+    }
+
+    async fn check_compliance(
+        &self,
+        news_id: &str,
+    ) -> Result<model::ComplianceReport, ServiceError> {
+        let html = get_html(news_id, &self.rv).await?;
+        self.check_compliance_from_text(&html).await
+    }
+
+    async fn fix_text(
+        &self,
+        html: &str,
+        templates: &LabelTemplates,
+    ) -> Result<model::FixedText, ServiceError> {
+        let stripped = StrippedHtml::new(html);
+        let report = self.compliance_report(html, &stripped).await?;
+        let (labels, text, html) = fixer::fix(html, &stripped.text, &report.mentions, templates);
+        Ok(model::FixedText {
+            generation: report.generation,
+            labels,
+            text,
+            html,
+        })
+    }
+
+    async fn fix_article(
+        &self,
+        news_id: &str,
+        templates: &LabelTemplates,
+    ) -> Result<model::FixedText, ServiceError> {
+        let html = get_html(news_id, &self.rv).await?;
+        self.fix_text(&html, templates).await
+    }
+}
+
+/// Все упоминания каждого имени в тексте и объединение имен одной сущности.
+///
+/// Упоминания ищутся по имени и дополняются совпадениями словаря `hits` с номером
+/// имени в `names`. Вложенные упоминания (`Навальный` внутри `Алексей Навальный`)
+/// отбрасываются. Смещения переводятся из `stripped.text` в исходный текст `source`
+fn group_mentions(
+    source: &str,
+    stripped: &StrippedHtml,
+    names: Vec<WarningName>,
+    hits: Vec<(usize, Range<usize>)>,
+    same_entity: impl Fn(&WarningName, &WarningName) -> bool,
+) -> Vec<WarningName> {
+    let normalized = normalize(&stripped.text);
+    let mut ranges: Vec<Vec<Range<usize>>> = names
+        .iter()
+        .map(|n| mention_ranges(&normalized, &n.name, &n.context))
+        .collect();
+    for (i, range) in hits {
+        ranges[i].push(range);
+    }
+
+    let mut grouped: Vec<(WarningName, Vec<Range<usize>>)> = Vec::new();
+    for (name, ranges) in names.into_iter().zip(ranges) {
+        let Some((group, group_ranges)) = grouped.iter_mut().find(|(g, _)| same_entity(g, &name))
+        else {
+            grouped.push((name, ranges));
+            continue;
+        };
+        group_ranges.extend(ranges);
+        for doc in name.docs {
+            if !group.docs.iter().any(|d| d.id == doc.id) {
+                group.docs.push(doc);
+            }
+        }
+        if group.source != name.source {
+            group.source = model::DetectionSource::Both;
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|(mut name, mut ranges)| {
+            name.docs = process_docs(name.docs);
+            ranges.sort_by_key(|r| (r.start, Reverse(r.end)));
+            let mut covered = 0;
+            ranges.retain(|r| {
+                let outside = r.start >= covered;
+                covered = covered.max(r.end);
+                outside
+            });
+            name.mentions = ranges
+                .into_iter()
+                .map(|r| mention(source, stripped, r))
+                .collect();
+            name
+        })
+        .collect()
+}
+
+/// Лучший документ каждого вида реестра, самые серьезные реестры первыми
+fn process_docs(docs: Vec<model::Doc>) -> Vec<model::Doc> {
+    let mut grouped: HashMap<RecordCategory, Vec<model::Doc>> = HashMap::new();
+
+    // 1. Разбиваем по status
+    for doc in docs {
+        grouped.entry(doc.status.clone()).or_default().push(doc);
+    }
+
+    let mut result = Vec::new();
+
+    // 2. Сортируем каждую группу и 3. Берем первый элемент
+    for (_, mut group) in grouped {
+        group.sort_by_key(|d| d.distance);
+        if let Some(first) = group.into_iter().next() {
+            result.push(first);
+        }
+    }
+
+    // 4. Сначала самые серьезные реестры
+    result.sort_by_key(|d| (std::cmp::Reverse(d.status.severity()), d.distance));
+    result
+}
+
+/// Упоминание с диапазоном `range` в тексте без разметки
+fn mention(source: &str, stripped: &StrippedHtml, range: Range<usize>) -> model::Mention {
+    let start = stripped.source_start(range.start);
+    let end = stripped.source_end(range.end);
+    let utf16 = |offset: usize| source[..offset].encode_utf16().count();
+    model::Mention {
+        text: stripped.text[range.clone()].to_string(),
+        start,
+        end,
+        start_utf16: utf16(start),
+        end_utf16: utf16(end),
+        range,
+    }
+}
+
+/// Слова имени без знаков препинания
+fn latin_words(name: &str) -> Vec<&str> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Сходство слов латинского имени `words` с написанием записи `form` в нижнем регистре.
+///
+/// Каждое слово имени должно быть целым словом записи, и среди них должно быть первое
+/// слово записи – фамилия персоны или начало названия. Поэтому одно `Sergey` не совпадает
+/// с каждым Сергеем реестра, а `Navalny` совпадает с `Навальный Алексей Анатольевич`.
+/// Сходство – Джаро–Винклер между именем и записью, слова которой переставлены
+/// в порядке имени: недостающие слова записи его снижают
+fn latin_similarity(words: &[&str], form: &str) -> Option<f32> {
+    let form_words = latin_words(form);
+    let first = *form_words.first()?;
+    if !words.contains(&first) || !words.iter().all(|w| form_words.contains(w)) {
+        return None;
+    }
+    let reordered: Vec<&str> = words
+        .iter()
+        .copied()
+        .chain(form_words.into_iter().filter(|w| !words.contains(w)))
+        .collect();
+    Some(strsim::jaro_winkler(&words.join(" "), &reordered.join(" ")) as f32)
+}
+
+/// Имя для поиска по индексу: русские буквы и точки. Имя латиницей
+/// (`Navalny`, `Meduza`) предварительно транслитерируется кириллицей
+fn query_name(name: &str) -> String {
+    match keep_russian_and_dot(name) {
+        russian if russian.is_empty() => keep_russian_and_dot(&to_cyrillic(name)),
+        russian => russian,
+    }
+}
+
+/// Итоговое решение по разбору: проходит ли запись пороги при поиске по индексу
+/// и находит ли ее прямой поиск по словарю
+fn decide(
+    record: &Record,
+    candidates: &[model::ExplainedCandidate],
+    thresholds: &model::Thresholds,
+    dictionary_match: bool,
+) -> model::Decision {
+    let dictionary = |reason: String| {
+        if dictionary_match {
+            model::Decision {
+                matched: true,
+                source: Some(model::DetectionSource::Dictionary),
+                reason: format!("{reason}, но запись найдена прямым поиском по словарю"),
+            }
+        } else {
+            model::Decision {
+                matched: false,
+                source: None,
+                reason,
+            }
+        }
+    };
+
+    if record.is_removed {
+        return dictionary("Запись исключена из реестра, поиск по индексу ее не возвращает".into());
+    }
+    if let Some(c) = candidates.iter().find(|c| c.passes) {
+        let source = if dictionary_match {
+            model::DetectionSource::Both
+        } else {
+            model::DetectionSource::Ner
+        };
+        return model::Decision {
+            matched: true,
+            source: Some(source),
+            reason: format!(
+                "Совпадение с «{}»: сходство {:.3} >= {}, расстояние {} <= {}",
+                c.registry_name,
+                c.similarity.unwrap_or_default(),
+                thresholds.max_treshold,
+                c.scoring.distance,
+                thresholds.max_dis
+            ),
+        };
+    }
+
+    // Причина отказа по самому близкому имени записи: сначала среди ближайших точек индекса
+    let best = candidates
+        .iter()
+        .filter(|c| c.similarity.is_some())
+        .max_by(|a, b| {
+            (a.in_nearest, a.similarity)
+                .partial_cmp(&(b.in_nearest, b.similarity))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    let reason = match best {
+        None => "У записи нет embedding".to_string(),
+        Some(c) if !c.in_nearest => format!(
+            "Запись не входит в {NEAREST} ближайших по embedding, сходство {:.3}",
+            c.similarity.unwrap_or_default()
+        ),
+        Some(c) if c.similarity.is_some_and(|s| s < thresholds.max_treshold) => format!(
+            "Сходство с «{}» {:.3} < {}",
+            c.registry_name,
+            c.similarity.unwrap_or_default(),
+            thresholds.max_treshold
+        ),
+        Some(c) => format!(
+            "Расстояние до «{}» {} > {}",
+            c.registry_name, c.scoring.distance, thresholds.max_dis
+        ),
+    };
+    dictionary(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn similarity(name: &str, registry_name: &str) -> Option<f32> {
+        let name = name.to_lowercase();
+        latin_similarity(&latin_words(&name), &to_latin(registry_name, Scheme::Bgn))
+    }
+
+    fn warning(name: &str, record_id: i64, source: model::DetectionSource) -> WarningName {
+        WarningName {
+            name: name.to_string(),
+            normal_name: name.to_string(),
+            context: String::new(),
+            name_type: "PER".to_string(),
+            docs: vec![model::Doc {
+                id: record_id,
+                status: RecordCategory::ForeignAgent,
+                name: name.to_string(),
+                is_removed: false,
+                similarity: 1.0,
+                distance: 0,
+                debug_distances: None,
+                matched_alias: None,
+            }],
+            source,
+            mentions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn mentions_of_one_record_are_grouped() {
+        let html = "<p>Алексей <b>Навальный</b> и Ёлкин</p>\n<p>Навальный заявил</p>";
+        let stripped = StrippedHtml::new(html);
+        let names = vec![
+            warning("Алексей Навальный", 1, model::DetectionSource::Ner),
+            warning("Навальный", 1, model::DetectionSource::Dictionary),
+            warning("Елкин", 2, model::DetectionSource::Ner),
+        ];
+        let same_record = |a: &WarningName, b: &WarningName| a.docs[0].id == b.docs[0].id;
+        let grouped = group_mentions(html, &stripped, names, Vec::new(), same_record);
+
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].source, model::DetectionSource::Both);
+        // `Навальный` внутри `Алексей Навальный` – не отдельное упоминание
+        let mentions: Vec<(&str, usize, usize)> = grouped[0]
+            .mentions
+            .iter()
+            .map(|m| (m.text.as_str(), m.start, m.end))
+            .collect();
+        let second = html.rfind("Навальный").unwrap();
+        assert_eq!(
+            mentions,
+            [
+                ("Алексей Навальный", 3, html.find("</b>").unwrap()),
+                ("Навальный", second, second + "Навальный".len()),
+            ]
+        );
+
+        let elkin = &grouped[1].mentions[0];
+        assert_eq!(elkin.text, "Ёлкин");
+        assert_eq!(&html[elkin.start..elkin.end], "Ёлкин");
+        let utf16 = html[..elkin.start].encode_utf16().count();
+        assert_eq!((elkin.start_utf16, elkin.end_utf16), (utf16, utf16 + 5));
+        assert_eq!(&stripped.text[elkin.range.clone()], "Ёлкин");
+    }
+
+    #[test]
+    fn given_name_alone_is_not_a_match() {
+        assert_eq!(similarity("Sergey", "Смирнов Сергей Сергеевич"), None);
+        assert_eq!(similarity("Dmitry", "Муратов Дмитрий Андреевич"), None);
+        assert_eq!(similarity("Sergeevich", "Смирнов Сергей Сергеевич"), None);
+    }
+
+    #[test]
+    fn whole_words_with_surname_match() {
+        let full = similarity("Sergey Smirnov", "Смирнов Сергей Сергеевич").unwrap();
+        let surname = similarity("Navalny", "Навальный Алексей Анатольевич").unwrap();
+        assert!(full > surname, "{full} {surname}");
+        assert_eq!(similarity("Meduza", "Медуза"), Some(1.0));
+        // Часть слова не считается совпадением
+        assert_eq!(similarity("Smirno", "Смирнов Сергей Сергеевич"), None);
+        assert_eq!(similarity("Ivan Smirnov", "Смирнов Сергей Сергеевич"), None);
     }
 }
@@ -1,6 +1,7 @@
 use crate::ino_checker::interface::SmartNameChecker;
-use crate::utils::funcs::unordered_levenshtein;
-use regex::Regex;
+use crate::ino_checker::model::NameComparison;
+use crate::utils::funcs::{extract_aliases, unordered_levenshtein};
+use crate::utils::morphology::name_stem;
 
 pub struct NameChecker {}
 
@@ -17,66 +18,82 @@ impl NameChecker {
             .join(" ")
     }
 
-    /// Простая нормализация русских фамилий, убираем окончания склонений
-    fn normalize_surname(&self, s: &str) -> String {
-        if s.ends_with("ой") || s.ends_with("ая") || s.ends_with("ой") || s.ends_with("овой")
-        {
-            return s[..s.len() - 2].to_string();
-        }
-        s.to_string()
-    }
-
-    /// Извлекает псевдонимы из кавычек
-    fn extract_aliases(&self, s: &str) -> Vec<String> {
-        let re = Regex::new(r#""([^"]+)""#).unwrap();
-        re.captures_iter(s).map(|cap| cap[1].to_string()).collect()
-    }
-
-    /// Выделяет фамилию (последнее слово) и инициалы (первые буквы остальных слов)
+    /// Выделяет фамилию (последнее слово длиннее двух букв) и инициалы.
+    /// Слово из одной-двух букв считается слитными инициалами: `ии иванов`, `иванов ии`
     fn split_name(&self, s: &str) -> (String, String) {
         let parts: Vec<&str> = s.split_whitespace().collect();
-        if parts.is_empty() {
+        let Some(pos) = parts
+            .iter()
+            .rposition(|w| w.chars().count() > 2)
+            .or(parts.len().checked_sub(1))
+        else {
             return ("".to_string(), "".to_string());
-        }
-        let surname = self.normalize_surname(parts.last().unwrap());
+        };
+        let surname = name_stem(parts[pos]);
         let initials: String = parts
             .iter()
-            .take(parts.len() - 1)
-            .filter_map(|w| w.chars().next())
+            .enumerate()
+            .filter(|(i, _)| *i != pos)
+            .flat_map(|(_, w)| {
+                let take = if w.chars().count() > 2 { 1 } else { 2 };
+                w.chars().take(take)
+            })
             .collect();
         (surname, initials)
     }
 }
 
 impl SmartNameChecker for NameChecker {
-    fn compare_names(&self, name_text: &str, name_registry: &str) -> usize {
+    fn compare_names(&self, name_text: &str, name_registry: &str) -> NameComparison {
         let text_norm = self.normalize_text(name_text);
-        let registry_norm = self.normalize_text(name_registry);
+        // Имя в реестре без псевдонимов: `Фамилия Имя Отчество`
+        let registry_base = name_registry.split(['"', '«']).next().unwrap_or_default();
+        let registry_norm = self.normalize_text(registry_base);
 
-        // Основная фамилия из реестра
+        // Основная фамилия и инициалы из реестра
         let registry_parts: Vec<&str> = registry_norm.split_whitespace().collect();
-        let main_surname = registry_parts.first().unwrap_or(&"");
+        let main_surname = registry_parts
+            .first()
+            .map_or(String::new(), |s| name_stem(s));
+        let registry_initials: String = registry_parts
+            .iter()
+            .skip(1)
+            .filter_map(|w| w.chars().next())
+            .collect();
 
         // Псевдонимы
-        let aliases = self.extract_aliases(name_registry);
-        let mut all_registry_surnames = vec![self.normalize_surname(main_surname)];
-        all_registry_surnames.extend(aliases.iter().map(|a| self.split_name(a).0));
+        let aliases = extract_aliases(name_registry);
+        let mut all_registry_surnames = vec![main_surname];
+        all_registry_surnames.extend(
+            aliases
+                .iter()
+                .map(|a| self.split_name(&self.normalize_text(a)).0),
+        );
 
         let (text_surname, text_initials) = self.split_name(&text_norm);
+        let initials_distance = unordered_levenshtein(&text_initials, &registry_initials);
 
         // Находим минимальное расстояние по фамилии + инициалам
-        let mut min_distance = usize::MAX;
+        let (best_surname, surname_distance) = all_registry_surnames
+            .iter()
+            .map(|surname| {
+                (
+                    surname.clone(),
+                    unordered_levenshtein(&text_surname, surname),
+                )
+            })
+            .min_by_key(|(_, d)| *d)
+            .unwrap_or_default();
 
-        for surname in all_registry_surnames {
-            let surname_dist = unordered_levenshtein(&text_surname, &surname);
-            let initials_dist =
-                unordered_levenshtein(&text_initials, &self.split_name(name_registry).1);
-            let total = surname_dist + initials_dist;
-            if total < min_distance {
-                min_distance = total;
-            }
+        NameComparison {
+            text_surname,
+            text_initials,
+            registry_surnames: all_registry_surnames,
+            registry_initials,
+            best_surname,
+            surname_distance,
+            initials_distance,
+            distance: surname_distance + initials_distance,
         }
-
-        min_distance
     }
 }
@@ -0,0 +1,389 @@
+use crate::db::model::Record;
+use crate::ino_checker::ann::ANNIndex;
+use crate::ino_checker::scanner::DictionaryScanner;
+use crate::utils::translit::{Scheme, to_latin};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Сколько ждать следующих изменений перед сохранением индекса
+const PERSIST_DELAY: Duration = Duration::from_secs(2);
+
+/// Все структуры, производные от списка запрещенных имен, построенные из одного снимка.
+///
+/// Не меняется после публикации: изменения вносятся в копию, которая подменяет
+/// снимок целиком. Поэтому индекс, поиск латинских имен, словарь и псевдонимы
+/// всегда соответствуют друг другу и номеру `generation`
+#[derive(Clone)]
+pub struct Registry {
+    pub generation: u64,
+    pub warning_names: Vec<Record>,
+    /// Имена и псевдонимы в нижнем регистре, в том же порядке, что и `warning_names`
+    lowercase_names: Vec<LowercaseNames>,
+    pub index: ANNIndex,
+    /// Прямой поиск имен реестра в тексте
+    pub scanner: DictionaryScanner,
+    /// Файл, в который сохраняется индекс после каждого изменения
+    index_path: Option<String>,
+    /// Индекс построен заново, а не загружен из файла, и его нужно сохранить
+    needs_persist: bool,
+}
+
+/// Имя записи и ее псевдонимы в нижнем регистре – для проверки латинских имен
+#[derive(Clone)]
+pub struct LowercaseNames {
+    pub name: String,
+    pub aliases: Vec<String>,
+    /// Транслитерация латиницей имени и псевдонимов по каждой системе `Scheme`
+    /// и номер псевдонима, `None` – основное имя
+    pub latin: Vec<(String, Option<usize>)>,
+}
+
+impl LowercaseNames {
+    fn new(rec: &Record) -> Self {
+        let names = std::iter::once((&rec.name, None)).chain(
+            rec.aliases
+                .iter()
+                .enumerate()
+                .map(|(i, a)| (&a.name, Some(i))),
+        );
+        let mut latin: Vec<(String, Option<usize>)> = Vec::new();
+        for (name, alias) in names {
+            for scheme in Scheme::ALL {
+                let transliterated = to_latin(name, scheme);
+                if !latin.iter().any(|(l, _)| *l == transliterated) {
+                    latin.push((transliterated, alias));
+                }
+            }
+        }
+        LowercaseNames {
+            name: rec.name.to_lowercase(),
+            aliases: rec.aliases.iter().map(|a| a.name.to_lowercase()).collect(),
+            latin,
+        }
+    }
+}
+
+impl Registry {
+    /// Построение всех структур по списку записей.
+    ///
+    /// Индекс загружается из `index_path`, если файл построен для этого же набора записей,
+    /// иначе строится заново, и тогда `needs_persist()` возвращает `true` – сохраняет
+    /// его вызывающий. Записи с embedding другой размерности в реестр не попадают
+    /// и возвращаются ошибками
+    pub fn build(
+        records: Vec<Record>,
+        dim: usize,
+        generation: u64,
+        index_path: Option<String>,
+    ) -> (Self, Vec<String>) {
+        let (records, mut errors) = check_dimensions(records, dim);
+        let loaded = match &index_path {
+            Some(path) => ANNIndex::load(path, dim, records.clone()).unwrap_or_else(|e| {
+                eprintln!("Failed to load index from {path}: {e}");
+                None
+            }),
+            None => None,
+        };
+
+        let (index, needs_persist) = match loaded {
+            Some(index) => {
+                println!("Loaded index with {} names from file", records.len());
+                (index, false)
+            }
+            None => {
+                println!("Adding {} names to index", records.len());
+                let (index, index_errors) =
+                    ANNIndex::from_records("warning-names", dim, records.clone());
+                errors.extend(index_errors);
+                (index, true)
+            }
+        };
+
+        // В списке остаются только записи, которые есть в индексе
+        let warning_names: Vec<Record> = records
+            .into_iter()
+            .filter(|r| index.contains(r.id))
+            .collect();
+        let registry = Registry {
+            generation,
+            lowercase_names: warning_names.iter().map(LowercaseNames::new).collect(),
+            scanner: DictionaryScanner::new(warning_names.iter()),
+            warning_names,
+            index,
+            index_path,
+            needs_persist,
+        };
+        (registry, errors)
+    }
+
+    /// Сохранение индекса на диск. Ошибка не мешает работе – при следующем
+    /// запуске индекс просто построится заново. Блокирует поток, из async-кода
+    /// снимок сохраняется через `IndexPersister`
+    pub fn persist(&self) {
+        if let Some(path) = &self.index_path
+            && let Err(e) = self.index.save(path)
+        {
+            eprintln!("Failed to save index to {path}: {e}");
+        }
+    }
+
+    pub fn needs_persist(&self) -> bool {
+        self.needs_persist
+    }
+
+    /// Перестроение словаря после изменения записей. Автомат не изменяется
+    /// по одной записи, поэтому вызывается один раз после всех изменений
+    pub fn rebuild_scanner(&mut self) {
+        self.scanner = DictionaryScanner::new(self.warning_names.iter());
+    }
+
+    pub fn record(&self, id: i64) -> Option<&Record> {
+        self.warning_names.iter().find(|r| r.id == id)
+    }
+
+    pub fn index_path(&self) -> Option<String> {
+        self.index_path.clone()
+    }
+
+    /// Записи вместе с именами в нижнем регистре
+    pub fn names(&self) -> impl Iterator<Item = (&Record, &LowercaseNames)> {
+        self.warning_names.iter().zip(self.lowercase_names.iter())
+    }
+
+    /// Хеш содержимого и флаг `is_removed` каждой записи – для сравнения с базой данных
+    pub fn fingerprints(&self) -> HashMap<i64, (u64, bool)> {
+        self.index.fingerprints()
+    }
+
+    pub fn dim(&self) -> usize {
+        self.index.dim()
+    }
+
+    /// Добавление записи или замена записи с тем же id.
+    ///
+    /// Запись с embedding другой размерности не добавляется, псевдонимы
+    /// другой размерности добавляются без embedding – в обоих случаях возвращается ошибка
+    pub fn upsert(&mut self, rec: Record) -> Result<(), anyhow::Error> {
+        let (mut checked, errors) = check_dimensions(vec![rec], self.dim());
+        if let Some(rec) = checked.pop() {
+            self.insert(rec)?;
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("{}", errors.join("; ")))
+        }
+    }
+
+    fn insert(&mut self, rec: Record) -> Result<(), anyhow::Error> {
+        self.index.add(rec.clone())?;
+        match self.warning_names.iter().position(|r| r.id == rec.id) {
+            Some(pos) => {
+                self.lowercase_names[pos] = LowercaseNames::new(&rec);
+                self.warning_names[pos] = rec;
+            }
+            None => {
+                self.lowercase_names.push(LowercaseNames::new(&rec));
+                self.warning_names.push(rec);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: i64) {
+        if let Some(pos) = self.warning_names.iter().position(|r| r.id == id) {
+            self.warning_names.remove(pos);
+            self.lowercase_names.remove(pos);
+        }
+        self.index.remove(id);
+    }
+
+    pub fn set_removed(&mut self, id: i64, is_removed: bool) {
+        if let Some(rec) = self.warning_names.iter_mut().find(|r| r.id == id) {
+            rec.is_removed = is_removed;
+        }
+        self.index.set_removed(id, is_removed);
+    }
+}
+
+/// Отложенное сохранение снимков реестра на диск.
+///
+/// Сохраняется только последний снимок после паузы `PERSIST_DELAY`, поэтому серия
+/// изменений записывает индекс один раз. Запись идет в `spawn_blocking` и не держит
+/// блокировок реестра, а следующая начинается только после предыдущей. Снимок
+/// не новее уже сохраненного или ожидающего отбрасывается, поэтому старый снимок
+/// не перезапишет файл более нового
+#[derive(Default)]
+pub struct IndexPersister {
+    state: Arc<Mutex<PersistState>>,
+}
+
+#[derive(Default)]
+struct PersistState {
+    /// Снимок, который еще не сохранен
+    latest: Option<Arc<Registry>>,
+    /// Задача сохранения уже запущена и заберет `latest`
+    running: bool,
+    /// Номер последнего сохраненного снимка
+    saved_generation: u64,
+}
+
+impl IndexPersister {
+    /// Сохранение снимка после паузы. Вызывается из среды tokio
+    pub fn schedule(&self, registry: Arc<Registry>) {
+        let mut state = self.state.lock().unwrap();
+        let pending = state.latest.as_ref().map_or(0, |r| r.generation);
+        if registry.generation <= state.saved_generation.max(pending) {
+            return;
+        }
+        state.latest = Some(registry);
+        if state.running {
+            return;
+        }
+        state.running = true;
+
+        let shared = self.state.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(PERSIST_DELAY).await;
+                let registry = {
+                    let mut state = shared.lock().unwrap();
+                    match state.latest.take() {
+                        Some(registry) => registry,
+                        None => {
+                            state.running = false;
+                            return;
+                        }
+                    }
+                };
+                let generation = registry.generation;
+                if let Err(e) = tokio::task::spawn_blocking(move || registry.persist()).await {
+                    eprintln!("Index persist task failed: {e}");
+                }
+                let mut state = shared.lock().unwrap();
+                state.saved_generation = state.saved_generation.max(generation);
+            }
+        });
+    }
+}
+
+/// Проверка размерности embedding записей и их псевдонимов.
+///
+/// Записи с основным вектором другой размерности отбрасываются, у псевдонимов
+/// такой вектор удаляется. О каждом случае возвращается ошибка
+pub fn check_dimensions(records: Vec<Record>, dim: usize) -> (Vec<Record>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut checked = Vec::with_capacity(records.len());
+
+    for mut rec in records {
+        if let Some(len) = rec.embedding.as_ref().map(Vec::len)
+            && len != dim
+        {
+            errors.push(format!(
+                "Запись {} ({}): embedding len {len} != dim {dim}",
+                rec.id, rec.name
+            ));
+            continue;
+        }
+        for alias in &mut rec.aliases {
+            if let Some(len) = alias.embedding.as_ref().map(Vec::len)
+                && len != dim
+            {
+                errors.push(format!(
+                    "Запись {} ({}), псевдоним {}: embedding len {len} != dim {dim}",
+                    rec.id, rec.name, alias.name
+                ));
+                alias.embedding = None;
+            }
+        }
+        checked.push(rec);
+    }
+
+    (checked, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::model::{Alias, RecordCategory};
+
+    fn record(id: i64, name: &str, embedding: Vec<f32>) -> Record {
+        Record {
+            id,
+            name: name.to_string(),
+            record_type: RecordCategory::ForeignAgent,
+            embedding: Some(embedding),
+            is_removed: false,
+            aliases: Vec::new(),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn index_file_of_other_records_is_rebuilt() {
+        let path = std::env::temp_dir().join(format!("registry-test-{}.hnsw", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let records = vec![
+            record(1, "Иванов", vec![1.0, 0.0, 0.0]),
+            record(2, "Петров", vec![0.0, 1.0, 0.0]),
+        ];
+
+        let (built, _) = Registry::build(records.clone(), 3, 1, Some(path.clone()));
+        assert!(built.needs_persist());
+        built.persist();
+
+        let (loaded, _) = Registry::build(records.clone(), 3, 1, Some(path.clone()));
+        assert!(!loaded.needs_persist());
+        let found = loaded.index.search(&[0.0, 1.0, 0.0], 1).unwrap();
+        assert_eq!(found[0].record.name, "Петров");
+
+        let mut changed = records;
+        changed.push(record(3, "Сидоров", vec![0.0, 0.0, 1.0]));
+        let (rebuilt, _) = Registry::build(changed, 3, 2, Some(path.clone()));
+        assert!(rebuilt.needs_persist());
+        let found = rebuilt.index.search(&[0.0, 0.0, 1.0], 1).unwrap();
+        assert_eq!(found[0].record.name, "Сидоров");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn older_snapshot_does_not_replace_pending_one() {
+        let (registry, _) =
+            Registry::build(vec![record(1, "Иванов", vec![1.0, 0.0, 0.0])], 3, 1, None);
+        let newer = Arc::new(Registry {
+            generation: 3,
+            ..registry.clone()
+        });
+        let older = Arc::new(Registry {
+            generation: 2,
+            ..registry
+        });
+
+        let persister = IndexPersister::default();
+        persister.schedule(newer);
+        persister.schedule(older);
+        let state = persister.state.lock().unwrap();
+        assert_eq!(state.latest.as_ref().map(|r| r.generation), Some(3));
+    }
+
+    #[test]
+    fn latin_names_in_both_schemes() {
+        let mut rec = record(1, "Ходорковский Михаил", vec![1.0]);
+        rec.aliases = vec![Alias {
+            name: "Медуза".to_string(),
+            embedding: None,
+        }];
+        let latin = LowercaseNames::new(&rec).latin;
+        assert_eq!(
+            latin,
+            [
+                ("khodorkovsky mikhail".to_string(), None),
+                ("xodorkovskij mixail".to_string(), None),
+                // Одинаковые в обеих системах написания не повторяются
+                ("meduza".to_string(), Some(0)),
+            ]
+        );
+    }
+}
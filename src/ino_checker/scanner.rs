@@ -0,0 +1,468 @@
+use crate::db::model::Record;
+use crate::ner::rules::{LEGAL_FORMS, quoted_spans};
+use crate::utils::morphology::{name_stem, surname_forms};
+use aho_corasick::AhoCorasick;
+use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Образцы короче не ищутся – слишком много случайных совпадений
+const MIN_PATTERN_CHARS: usize = 3;
+/// Фамилия без имени ищется, только если она не короче
+const MIN_SURNAME_CHARS: usize = 4;
+
+/// Чем образец словаря связан с записью реестра
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatternKind {
+    /// Полное имя записи или название в кавычках
+    Name,
+    /// Псевдоним с номером в `Record::aliases`
+    Alias(usize),
+    /// Фамилия в одной из падежных форм рядом с именем или инициалами записи
+    Surname,
+}
+
+/// Запись реестра, к которой ведет образец
+#[derive(Clone, Copy)]
+struct PatternTarget {
+    record_id: i64,
+    kind: PatternKind,
+    /// `PER` для записей-персон, иначе `ORG`
+    entity_type: &'static str,
+}
+
+/// Имя персоны, без которого фамилия не считается совпадением
+#[derive(Clone)]
+struct GivenName {
+    /// Основа имени (`name_stem`), чтобы узнавать его в любом падеже
+    stem: String,
+    initial: char,
+}
+
+/// Совпадение образца словаря с текстом
+#[derive(Debug)]
+pub struct DictionaryHit {
+    pub record_id: i64,
+    pub kind: PatternKind,
+    pub entity_type: &'static str,
+    /// Диапазон совпадения в байтах исходного текста
+    pub range: Range<usize>,
+}
+
+/// Поиск имен реестра прямо в тексте, без сервиса NER.
+///
+/// Автомат Ахо-Корасик строится по именам, псевдонимам и формам фамилий всех записей.
+/// Текст и образцы сравниваются в нижнем регистре и без `ё`
+#[derive(Clone)]
+pub struct DictionaryScanner {
+    automaton: Option<AhoCorasick>,
+    /// Записи каждого образца, в порядке образцов автомата
+    targets: Vec<Vec<PatternTarget>>,
+    /// Имена персон по id записи
+    given_names: HashMap<i64, GivenName>,
+    /// Инициалы перед фамилией: `И.И. Иванов`, `И. Иванов`
+    initials_before: Regex,
+    /// Инициалы после фамилии: `Иванов И.И.`
+    initials_after: Regex,
+}
+
+impl DictionaryScanner {
+    pub fn new<'a>(records: impl Iterator<Item = &'a Record>) -> Self {
+        let mut patterns: Vec<String> = Vec::new();
+        let mut targets: Vec<Vec<PatternTarget>> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut given_names = HashMap::new();
+
+        for rec in records {
+            if let Some(given) = person_name(&rec.name).and_then(|p| p.given.into_iter().next()) {
+                given_names.insert(
+                    rec.id,
+                    GivenName {
+                        stem: name_stem(&given),
+                        initial: given.chars().next().unwrap_or_default(),
+                    },
+                );
+            }
+            let (patterns_of_record, entity_type) = record_patterns(rec);
+            for (pattern, kind) in patterns_of_record {
+                let pos = *positions.entry(pattern.clone()).or_insert_with(|| {
+                    patterns.push(pattern);
+                    targets.push(Vec::new());
+                    patterns.len() - 1
+                });
+                if !targets[pos].iter().any(|t| t.record_id == rec.id) {
+                    targets[pos].push(PatternTarget {
+                        record_id: rec.id,
+                        kind,
+                        entity_type,
+                    });
+                }
+            }
+        }
+
+        let automaton = match AhoCorasick::new(&patterns) {
+            Ok(a) => Some(a),
+            Err(e) => {
+                eprintln!("Failed to build dictionary scanner: {e}");
+                None
+            }
+        };
+        DictionaryScanner {
+            automaton,
+            targets,
+            given_names,
+            initials_before: Regex::new(r"(?:^|[^\w.])(\w)\.(?:\s*\w\.)?\s*$").unwrap(),
+            initials_after: Regex::new(r"^\s*(\w)\.(?:\s*\w\.)?").unwrap(),
+        }
+    }
+
+    /// Все совпадения с границами слов. Из пересекающихся совпадений остается
+    /// самое раннее, а из начинающихся в одном месте – самое длинное.
+    ///
+    /// Фамилия без имени или инициалов записи рядом не считается совпадением:
+    /// `Белый дом` не упоминание Белого Руслана. Диапазон такого совпадения
+    /// включает имя или инициалы
+    pub fn scan(&self, text: &str) -> Vec<DictionaryHit> {
+        let Some(automaton) = &self.automaton else {
+            return Vec::new();
+        };
+        let normalized = normalize(text);
+
+        let mut found: Vec<(Range<usize>, usize)> = automaton
+            .find_overlapping_iter(&normalized)
+            .filter(|m| is_word_boundary(&normalized, m.start(), m.end()))
+            .map(|m| (m.range(), m.pattern().as_usize()))
+            .collect();
+        found.sort_by_key(|(range, _)| (range.start, Reverse(range.end)));
+
+        let mut hits = Vec::new();
+        let mut covered = 0;
+        for (range, pattern) in found {
+            if range.start < covered {
+                continue;
+            }
+            covered = range.end;
+            for target in &self.targets[pattern] {
+                let range = match target.kind {
+                    PatternKind::Surname => {
+                        match self.with_given_name(&normalized, range.clone(), target.record_id) {
+                            Some(range) => range,
+                            None => continue,
+                        }
+                    }
+                    PatternKind::Name | PatternKind::Alias(_) => range.clone(),
+                };
+                covered = covered.max(range.end);
+                hits.push(DictionaryHit {
+                    record_id: target.record_id,
+                    kind: target.kind,
+                    entity_type: target.entity_type,
+                    range,
+                });
+            }
+        }
+        hits
+    }
+
+    /// Диапазон фамилии вместе с именем или инициалами записи `record_id`
+    /// перед ней или после нее. `None`, если рядом их нет
+    fn with_given_name(
+        &self,
+        normalized: &str,
+        surname: Range<usize>,
+        record_id: i64,
+    ) -> Option<Range<usize>> {
+        let given = self.given_names.get(&record_id)?;
+        let before = &normalized[..surname.start];
+        let after = &normalized[surname.end..];
+
+        let word_before = before.trim_end();
+        let word_before = word_before
+            .rfind(|c: char| !c.is_alphabetic())
+            .map_or(word_before, |i| &word_before[i + 1..]);
+        if !word_before.is_empty() && name_stem(word_before) == given.stem {
+            let start = before.trim_end().len() - word_before.len();
+            return Some(start..surname.end);
+        }
+        let word_after = after.trim_start();
+        let word_after = word_after
+            .find(|c: char| !c.is_alphabetic())
+            .map_or(word_after, |i| &word_after[..i]);
+        if !word_after.is_empty() && name_stem(word_after) == given.stem {
+            let end = surname.end + (after.len() - after.trim_start().len()) + word_after.len();
+            return Some(surname.start..end);
+        }
+
+        let is_initial = |m: Option<regex::Match>| {
+            m.and_then(|m| m.as_str().chars().next()) == Some(given.initial)
+        };
+        if let Some(caps) = self.initials_before.captures(before)
+            && is_initial(caps.get(1))
+        {
+            return Some(caps.get(1)?.start()..surname.end);
+        }
+        if let Some(caps) = self.initials_after.captures(after)
+            && is_initial(caps.get(1))
+        {
+            return Some(surname.start..surname.end + caps.get(0)?.end());
+        }
+        None
+    }
+}
+
+/// Нижний регистр и `е` вместо `ё`. Символы, у которых длина в байтах при этом
+/// меняется, остаются как есть, поэтому смещения совпадают с исходным текстом
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some('ё'), None) => 'е',
+                (Some(l), None) if l.len_utf8() == c.len_utf8() => l,
+                _ => c,
+            }
+        })
+        .collect()
+}
+
+/// Все вхождения имени в `normalized` (текст после `normalize`) по границам слов.
+/// Если имени в тексте нет в том же написании (нормальная форма из NER),
+/// ищется контекст, а в нем – самое длинное слово имени
+pub fn mention_ranges(normalized: &str, name: &str, context: &str) -> Vec<Range<usize>> {
+    let name = normalize(name.trim());
+    let ranges = find_words(normalized, &name);
+    if !ranges.is_empty() {
+        return ranges;
+    }
+
+    let context = normalize(context.trim());
+    let Some(context_start) = (!context.is_empty())
+        .then(|| normalized.find(&context))
+        .flatten()
+    else {
+        return Vec::new();
+    };
+    let longest = name
+        .split_whitespace()
+        .max_by_key(|w| w.chars().count())
+        .unwrap_or_default();
+    find_words(&context, longest)
+        .into_iter()
+        .take(1)
+        .map(|r| context_start + r.start..context_start + r.end)
+        .collect()
+}
+
+fn find_words(haystack: &str, needle: &str) -> Vec<Range<usize>> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    haystack
+        .match_indices(needle)
+        .map(|(i, _)| i..i + needle.len())
+        .filter(|r| is_word_boundary(haystack, r.start, r.end))
+        .collect()
+}
+
+fn is_word_boundary(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+/// Образцы одной записи: для персон – ФИО в нескольких порядках и формы фамилии,
+/// для организаций – полное название, название до скобок и названия в кавычках. Плюс все псевдонимы.
+/// Вторым значением возвращается тип имени: `PER` или `ORG`
+fn record_patterns(rec: &Record) -> (Vec<(String, PatternKind)>, &'static str) {
+    let mut patterns = Vec::new();
+
+    let person = person_name(&rec.name);
+    let entity_type = if person.is_some() { "PER" } else { "ORG" };
+    match person {
+        Some(person) => {
+            // Полное ФИО и имя с фамилией без отчества, в обоих порядках
+            for given in [person.given.join(" "), person.given[0].clone()] {
+                patterns.push((format!("{} {given}", person.surname), PatternKind::Name));
+                patterns.push((format!("{given} {}", person.surname), PatternKind::Name));
+            }
+            if person.surname.chars().count() >= MIN_SURNAME_CHARS {
+                for form in surname_forms(&person.surname, person.is_female) {
+                    patterns.push((form, PatternKind::Surname));
+                }
+            }
+        }
+        None => {
+            let name = normalize(&rec.name);
+            patterns.push((
+                name.split_whitespace().collect::<Vec<_>>().join(" "),
+                PatternKind::Name,
+            ));
+            // Название без английского варианта или страны в скобках:
+            // `Фонд Открытое общество (Open Society Foundation)`
+            if let Some((head, _)) = name.split_once(" (") {
+                patterns.push((
+                    head.split_whitespace().collect::<Vec<_>>().join(" "),
+                    PatternKind::Name,
+                ));
+            }
+            for span in quoted_spans(&name) {
+                let quoted = &name[span];
+                let inner = quoted
+                    .trim_start_matches(['«', '"'])
+                    .trim_end_matches(['»', '"'])
+                    .trim();
+                patterns.push((quoted.to_string(), PatternKind::Name));
+                patterns.push((format!("\"{inner}\""), PatternKind::Name));
+            }
+        }
+    }
+
+    for (i, alias) in rec.aliases.iter().enumerate() {
+        patterns.push((normalize(alias.name.trim()), PatternKind::Alias(i)));
+    }
+
+    patterns.retain(|(p, _)| p.trim_matches(['«', '»', '"']).chars().count() >= MIN_PATTERN_CHARS);
+    (patterns, entity_type)
+}
+
+/// ФИО персоны в нижнем регистре
+struct PersonName {
+    surname: String,
+    /// Имя и отчество
+    given: Vec<String>,
+    /// `None`, если пол не понятен по отчеству
+    is_female: Option<bool>,
+}
+
+/// Разбор имени записи как ФИО: два-три слова кириллицей с заглавной буквы,
+/// первое – фамилия. Псевдонимы в кавычках отбрасываются
+fn person_name(name: &str) -> Option<PersonName> {
+    let name = name.split(['"', '«']).next().unwrap_or_default();
+    let words: Vec<&str> = name.split_whitespace().collect();
+    if !(2..=3).contains(&words.len()) || !words.iter().all(|w| is_title_case(w)) {
+        return None;
+    }
+    let words: Vec<String> = words.iter().map(|w| normalize(w)).collect();
+    if LEGAL_FORMS.contains(&words[0].as_str()) {
+        return None;
+    }
+
+    let patronymic = words.get(2).map(String::as_str).unwrap_or_default();
+    let is_female = if ["вна", "чна", "кызы"]
+        .iter()
+        .any(|s| patronymic.ends_with(s))
+    {
+        Some(true)
+    } else if ["вич", "оглы", "улы"]
+        .iter()
+        .any(|s| patronymic.ends_with(s))
+    {
+        Some(false)
+    } else {
+        None
+    };
+
+    Some(PersonName {
+        surname: words[0].clone(),
+        given: words[1..].to_vec(),
+        is_female,
+    })
+}
+
+/// Слово кириллицей с заглавной буквы, остальные строчные; допускается дефис
+fn is_title_case(word: &str) -> bool {
+    word.split('-').all(|part| {
+        let mut chars = part.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_uppercase() && is_cyrillic(c))
+            && chars.all(|c| c.is_lowercase() && is_cyrillic(c))
+    })
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, 'А'..='я' | 'Ё' | 'ё')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanner(names: &[&str]) -> DictionaryScanner {
+        let records: Vec<Record> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Record {
+                id: i as i64 + 1,
+                name: name.to_string(),
+                record_type: Default::default(),
+                embedding: None,
+                is_removed: false,
+                aliases: Vec::new(),
+                created_at: None,
+                updated_at: None,
+            })
+            .collect();
+        DictionaryScanner::new(records.iter())
+    }
+
+    /// Найденные записи и совпавший текст
+    fn found<'a>(scanner: &DictionaryScanner, text: &'a str) -> Vec<(i64, &'a str)> {
+        scanner
+            .scan(text)
+            .into_iter()
+            .map(|h| (h.record_id, &text[h.range]))
+            .collect()
+    }
+
+    #[test]
+    fn surname_alone_is_not_a_hit() {
+        let scanner = scanner(&["Белый Руслан Викторович", "Иванов Иван Иванович"]);
+        for text in [
+            "Белый дом заявил",
+            "у Белого дома",
+            "перед белым домом",
+            "Петр Иванов пришел",
+            "Иванову позвонили",
+            "А. Иванов",
+        ] {
+            assert_eq!(found(&scanner, text), [], "{text}");
+        }
+    }
+
+    #[test]
+    fn surname_with_given_name_or_initials() {
+        let scanner = scanner(&["Белый Руслан Викторович", "Иванов Иван Иванович"]);
+        #[rustfmt::skip]
+        let cases = [
+            ("Руслан Белый выступил", (1, "Руслан Белый")),
+            ("встреча с Русланом Белым", (1, "Русланом Белым")),
+            ("Белого Руслана нет", (1, "Белого Руслана")),
+            ("Р.В. Белый", (1, "Р.В. Белый")),
+            ("Белый Р. В. выступил", (1, "Белый Р. В.")),
+            ("письмо Ивану Иванову", (2, "Ивану Иванову")),
+            ("И.И. Иванова", (2, "И.И. Иванова")),
+        ];
+        for (text, hit) in cases {
+            assert_eq!(found(&scanner, text), [hit], "{text}");
+        }
+    }
+
+    #[test]
+    fn full_names_and_organizations() {
+        let scanner = scanner(&[
+            "Скворцова Елена Сергеевна",
+            "Фонд борьбы с коррупцией (ФБК)",
+        ]);
+        assert_eq!(
+            found(&scanner, "Об этом рассказала Елена Скворцова."),
+            [(1, "Елена Скворцова")]
+        );
+        assert_eq!(
+            found(&scanner, "ФОНД БОРЬБЫ С КОРРУПЦИЕЙ сообщил"),
+            [(2, "ФОНД БОРЬБЫ С КОРРУПЦИЕЙ")]
+        );
+        assert_eq!(found(&scanner, "Скворцовая Елена"), []);
+    }
+}
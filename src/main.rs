@@ -1,8 +1,11 @@
 mod db;
 mod embedding;
+mod error;
+mod import;
 mod ino_api;
 mod ino_checker;
 mod ner;
+mod resilience;
 mod rv;
 mod utils;
 
@@ -13,26 +16,48 @@ use crate::ino_api::handlers;
 use actix_web::middleware::Logger;
 use actix_web::{App, HttpServer, web};
 
+use crate::ner::backend::EntitiesBackend;
 use crate::ner::entities::PythonEntities;
+use crate::ner::rules::RuleEntities;
 use std::env;
 
 use crate::ino_checker::new_checker::WarningNamesChecker;
 use crate::ino_checker::new_name_checker::NameChecker;
 
+use crate::db::embedding_cache::EmbeddingCacheTable;
+use crate::embedding::backend::EmbeddingBackend;
+use crate::embedding::cache::CachedEmbedding;
+use crate::embedding::local::LocalEmbedding;
+use crate::embedding::model::{DEFAULT_EMBEDDING_DIM, DEFAULT_MAX_CONCURRENCY};
 use crate::embedding::vectorize::YandexEmbedding;
 
 use crate::db::interface::DB;
 use crate::db::sqlite::Database;
 
+use crate::db::model::RecordCategory;
+use crate::import::importer::{import_records, reembed_records};
+use crate::import::interface::RegistryReader;
+use crate::import::kinds::registry_kind;
+use crate::import::xlsx::MinjustXlsx;
 use crate::ino_api::server_api::Checker;
+use crate::resilience::model::ResilienceConfig;
+use crate::resilience::upstream::Upstream;
 
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use std::io::Write;
+use std::num::NonZeroUsize;
+use std::time::Duration;
 
 const ADDR: &str = "0.0.0.0";
 const PORT: u16 = 8080;
+const DB_PATH: &str = "assets/db/ino.sqlite";
+const INDEX_PATH: &str = "assets/db/ino.hnsw";
+const DEFAULT_IMPORT_DIR: &str = "assets";
+const DEFAULT_CACHE_SIZE: NonZeroUsize = NonZeroUsize::new(10_000).unwrap();
+const DEFAULT_CACHE_TTL_SECS: u64 = 30 * 24 * 60 * 60;
 
 #[actix_web::main]
 async fn main() -> Result<(), std::io::Error> {
@@ -40,27 +65,55 @@ async fn main() -> Result<(), std::io::Error> {
     dotenv().ok();
     env_logger::init();
 
-    let entities_url = env::var("ENTITIES_URL").ok().unwrap();
-    let rv_entities = PythonEntities::new(entities_url);
+    let db = Arc::new(Mutex::new(Database::new(DB_PATH).ok().unwrap()));
+
+    let dim = embedding_dim(&*db.lock().await)?;
+    println!("Размерность embedding: {dim}");
+    let yandex_upstream = Upstream::new("yandex", ResilienceConfig::from_env("YANDEX"));
+    let backend = embedding_backend(dim, yandex_upstream.clone())?;
+
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("import") => {
+            let vectorizer = embedding_cache(backend);
+            return run_import(&args[2..], &*db.lock().await, &vectorizer).await;
+        }
+        Some("reembed") => return run_reembed(&*db.lock().await, &backend).await,
+        _ => {}
+    }
+    let vectorizer = embedding_cache(backend);
+
+    let ner_upstream = Upstream::new("ner", ResilienceConfig::from_env("NER"));
+    let rv_upstream = Upstream::new("rv", ResilienceConfig::from_env("RV"));
+    let rv_entities = entities_backend(ner_upstream.clone());
     let name_checker = NameChecker::new();
 
-    let model = env::var("YANDEX_MODEL").ok().unwrap();
-    let token = env::var("YANDEX_SECRET").ok().unwrap();
-    let url = env::var("YANDEX_URL").ok().unwrap();
-    let yandex_embedding = YandexEmbedding::new(model, token, url);
-    let db = Arc::new(Mutex::new(
-        Database::new("assets/db/ino.sqlite").ok().unwrap(),
-    ));
     let warning_names = db.lock().await.get_all().ok().unwrap();
-    let warning_name_checker =
-        WarningNamesChecker::new(warning_names, yandex_embedding, name_checker, rv_entities);
+    let warning_name_checker = WarningNamesChecker::new(
+        warning_names,
+        vectorizer.clone(),
+        name_checker,
+        rv_entities,
+        Some(INDEX_PATH.to_string()),
+        rv_upstream.clone(),
+    );
     println!("Запуск сервера по адресу {ADDR}:{PORT}");
     std::io::stdout().flush().unwrap();
 
     let need_full_data = true;
 
-    let api_checker = Checker::new(need_full_data, warning_name_checker, db).unwrap();
+    let api_checker = Checker::new(
+        need_full_data,
+        warning_name_checker,
+        db,
+        vectorizer,
+        env::var("IMPORT_DIR")
+            .unwrap_or_else(|_| DEFAULT_IMPORT_DIR.to_string())
+            .into(),
+    )
+    .unwrap();
     let checker_data = web::Data::new(api_checker);
+    let upstreams = web::Data::new(vec![yandex_upstream, ner_upstream, rv_upstream]);
 
     HttpServer::new(move || {
         App::new()
@@ -68,12 +121,183 @@ async fn main() -> Result<(), std::io::Error> {
                 "%a %t \"%r\" %s %b \"%{User-Agent}i\" %Dms \"%{Content-Type}i\"",
             ))
             .app_data(checker_data.clone())
+            .app_data(upstreams.clone())
             .route("/check/{id}", web::get().to(handlers::check_by_id_handler))
             .route("/check", web::post().to(handlers::check_by_text))
+            .route(
+                "/compliance/{id}",
+                web::get().to(handlers::compliance_by_id),
+            )
+            .route("/compliance", web::post().to(handlers::compliance_by_text))
+            .route("/fix", web::post().to(handlers::fix))
+            .route("/explain", web::post().to(handlers::explain))
             .route("/update", web::get().to(handlers::update_inos))
             .route("/add", web::post().to(handlers::add_new_names))
+            .route("/import", web::post().to(handlers::import_registry))
+            .route(
+                "/records/{id}/remove",
+                web::post().to(handlers::remove_record),
+            )
+            .route(
+                "/records/{id}/restore",
+                web::post().to(handlers::restore_record),
+            )
+            .route("/categories", web::get().to(handlers::list_categories))
+            .route("/records", web::get().to(handlers::list_records))
+            .route("/records/{id}", web::get().to(handlers::get_record))
+            .route("/records/{id}", web::put().to(handlers::update_record))
+            .route("/records/{id}", web::delete().to(handlers::delete_record))
+            .route(
+                "/embedding/cache",
+                web::get().to(handlers::embedding_cache_stats),
+            )
+            .route("/health", web::get().to(handlers::health))
     })
     .bind((ADDR, PORT))?
     .run()
     .await
 }
+
+/// Размерность embedding: из переменной `EMBEDDING_DIM`, иначе самая частая
+/// размерность в базе, иначе размерность модели Yandex по умолчанию
+fn embedding_dim(db: &Database) -> Result<usize, std::io::Error> {
+    if let Ok(dim) = env::var("EMBEDDING_DIM") {
+        return dim.parse().ok().filter(|&d| d > 0).ok_or_else(|| {
+            std::io::Error::other(format!(
+                "EMBEDDING_DIM должна быть положительным числом: {dim}"
+            ))
+        });
+    }
+    Ok(db
+        .embedding_dim()
+        .ok()
+        .flatten()
+        .unwrap_or(DEFAULT_EMBEDDING_DIM))
+}
+
+/// Способ векторизации из переменной `EMBEDDING_BACKEND`: `yandex` (по умолчанию) или `local`
+fn embedding_backend(dim: usize, upstream: Upstream) -> Result<EmbeddingBackend, std::io::Error> {
+    match env::var("EMBEDDING_BACKEND").as_deref() {
+        Ok("local") => Ok(EmbeddingBackend::Local(LocalEmbedding::new(dim))),
+        Ok("yandex") | Err(_) => {
+            let max_concurrency = env::var("YANDEX_MAX_CONCURRENCY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_CONCURRENCY);
+            Ok(EmbeddingBackend::Yandex(YandexEmbedding::new(
+                required_var("YANDEX_MODEL")?,
+                required_var("YANDEX_SECRET")?,
+                required_var("YANDEX_URL")?,
+                dim,
+                max_concurrency,
+                upstream,
+            )))
+        }
+        Ok(other) => Err(std::io::Error::other(format!(
+            "Неизвестный EMBEDDING_BACKEND: {other}, допустимы yandex и local"
+        ))),
+    }
+}
+
+fn required_var(name: &str) -> Result<String, std::io::Error> {
+    env::var(name).map_err(|_| std::io::Error::other(format!("Не задана переменная {name}")))
+}
+
+/// Извлечение имен: сервис `ENTITIES_URL` с переходом на правила при его недоступности.
+/// `NER_FALLBACK=false` отключает переход, без `ENTITIES_URL` используются только правила
+fn entities_backend(upstream: Upstream) -> EntitiesBackend {
+    let Ok(url) = env::var("ENTITIES_URL") else {
+        println!("ENTITIES_URL не задан, имена извлекаются правилами");
+        return EntitiesBackend::Rules(RuleEntities::new());
+    };
+    let python = PythonEntities::new(url, upstream);
+    match env::var("NER_FALLBACK").as_deref() {
+        Ok("false") | Ok("0") => EntitiesBackend::Python(python),
+        _ => EntitiesBackend::PythonWithFallback(python, RuleEntities::new()),
+    }
+}
+
+/// Кеш embedding: размер LRU из `EMBEDDING_CACHE_SIZE`, срок жизни в секундах
+/// из `EMBEDDING_CACHE_TTL`. Таблица в SQLite отключается через `EMBEDDING_CACHE_SQLITE=false`
+fn embedding_cache(backend: EmbeddingBackend) -> CachedEmbedding<EmbeddingBackend> {
+    let capacity = env::var("EMBEDDING_CACHE_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .and_then(NonZeroUsize::new)
+        .unwrap_or(DEFAULT_CACHE_SIZE);
+    let ttl = env::var("EMBEDDING_CACHE_TTL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CACHE_TTL_SECS);
+
+    let use_sqlite = env::var("EMBEDDING_CACHE_SQLITE").map_or(true, |v| v != "false" && v != "0");
+    let table = if use_sqlite {
+        EmbeddingCacheTable::open(DB_PATH, ttl)
+            .map_err(|e| eprintln!("Failed to open embedding cache table: {e}"))
+            .ok()
+    } else {
+        None
+    };
+
+    CachedEmbedding::new(backend, capacity, Duration::from_secs(ttl), table)
+}
+
+/// Импорт выгрузки реестра из командной строки: `service import <path> [record_type]`
+async fn run_import(
+    args: &[String],
+    db: &Database,
+    vectorizer: &CachedEmbedding<EmbeddingBackend>,
+) -> Result<(), std::io::Error> {
+    let Some(path) = args.first() else {
+        return Err(std::io::Error::other(
+            "usage: service import <path> [record_type]",
+        ));
+    };
+    let record_type = args
+        .get(1)
+        .cloned()
+        .map(RecordCategory::from)
+        .unwrap_or_default();
+    record_type
+        .validate()
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    let kind = registry_kind(&record_type);
+    let records = MinjustXlsx::new(record_type)
+        .read_records(Path::new(path))
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    println!("{}, прочитано записей: {}", kind.title, records.len());
+
+    let report = import_records(db, vectorizer, records)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    println!(
+        "Добавлено: {}, обновлено: {}, без изменений: {}, ошибок: {}",
+        report.inserted,
+        report.updated,
+        report.unchanged,
+        report.errors.len()
+    );
+    for e in report.errors {
+        eprintln!("{e}");
+    }
+    Ok(())
+}
+
+/// Пересчет embedding всех записей текущей моделью: `service reembed`.
+/// Модель вызывается напрямую, без кеша: после смены модели или способа
+/// векторизации в кеше могут остаться старые векторы
+async fn run_reembed(db: &Database, vectorizer: &EmbeddingBackend) -> Result<(), std::io::Error> {
+    let report = reembed_records(db, vectorizer)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    println!(
+        "Пересчитано: {}, ошибок: {}",
+        report.updated,
+        report.errors.len()
+    );
+    for e in report.errors {
+        eprintln!("{e}");
+    }
+    Ok(())
+}
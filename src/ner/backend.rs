@@ -0,0 +1,34 @@
+use crate::error::model::ServiceError;
+use crate::ner::entities::PythonEntities;
+use crate::ner::interface::Entities;
+use crate::ner::model::Response;
+use crate::ner::rules::RuleEntities;
+
+/// Способ извлечения имен, выбранный в конфигурации
+pub enum EntitiesBackend {
+    /// Только внешний сервис NER
+    Python(PythonEntities),
+    /// Только правила, без внешнего сервиса
+    Rules(RuleEntities),
+    /// Внешний сервис, а при его недоступности, сбое или таймауте
+    /// (`NER_TIMEOUT_SECS`) – правила
+    PythonWithFallback(PythonEntities, RuleEntities),
+}
+
+impl Entities for EntitiesBackend {
+    async fn get_entities(&self, text: &str) -> Result<Response, ServiceError> {
+        match self {
+            EntitiesBackend::Python(e) => e.get_entities(text).await,
+            EntitiesBackend::Rules(e) => e.get_entities(text).await,
+            EntitiesBackend::PythonWithFallback(python, rules) => {
+                match python.get_entities(text).await {
+                    Err(e @ ServiceError::Upstream { .. }) => {
+                        eprintln!("{e}, falling back to rule-based NER");
+                        rules.get_entities(text).await
+                    }
+                    result => result,
+                }
+            }
+        }
+    }
+}
@@ -1,24 +1,38 @@
+use crate::error::model::ServiceError;
 use crate::ner::interface::Entities;
 use crate::ner::model::{Request, Response};
+use crate::resilience::model::Failure;
+use crate::resilience::upstream::Upstream;
 
 pub struct PythonEntities {
     url: String,
+    /// Ограничитель, повторы и предохранитель для запросов к сервису NER
+    upstream: Upstream,
 }
 
 impl PythonEntities {
-    pub fn new(url: String) -> Self {
-        PythonEntities { url }
+    pub fn new(url: String, upstream: Upstream) -> Self {
+        PythonEntities { url, upstream }
     }
 }
 
 impl Entities for PythonEntities {
-    async fn get_entities(&self, text: &str) -> Result<Response, anyhow::Error> {
-        let client = reqwest::Client::new();
+    async fn get_entities(&self, text: &str) -> Result<Response, ServiceError> {
         let req = Request {
             text: text.to_string(),
         };
-        let resp = client.post(&self.url).json(&req).send().await?;
-        let res: Response = resp.json().await?;
-        Ok(res)
+        self.upstream
+            .call(|| async {
+                let client = self.upstream.client();
+                let resp = client.post(&self.url).json(&req).send().await?;
+
+                let status = resp.status();
+                if !status.is_success() {
+                    return Err(Failure::from_status(status, format!("NER {status}")));
+                }
+                let res: Response = resp.json().await?;
+                Ok(res)
+            })
+            .await
     }
 }
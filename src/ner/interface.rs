@@ -1,4 +1,5 @@
+use crate::error::model::ServiceError;
 use crate::ner::model;
 pub trait Entities {
-    async fn get_entities(&self, text: &str) -> Result<model::Response, anyhow::Error>;
+    async fn get_entities(&self, text: &str) -> Result<model::Response, ServiceError>;
 }
@@ -1,3 +1,5 @@
+pub mod backend;
 pub mod entities;
 pub mod interface;
 pub mod model;
+pub mod rules;
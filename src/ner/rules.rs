@@ -0,0 +1,301 @@
+use crate::error::model::ServiceError;
+use crate::ner::interface::Entities;
+use crate::ner::model::{Entity, Response};
+use crate::utils::funcs::text_context;
+use regex::Regex;
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Организационно-правовые формы и слова, которые стоят перед названием в кавычках
+pub const LEGAL_FORMS: [&str; 20] = [
+    "ооо",
+    "оао",
+    "ао",
+    "зао",
+    "пао",
+    "ано",
+    "нко",
+    "фонд",
+    "движение",
+    "издание",
+    "интернет-издание",
+    "организация",
+    "ассоциация",
+    "союз",
+    "центр",
+    "проект",
+    "партия",
+    "общество",
+    "объединение",
+    "студия",
+];
+
+/// Слова с заглавной буквы, которые часто начинают предложение, но не входят в имя
+const STOP_WORDS: [&str; 35] = [
+    "в",
+    "во",
+    "на",
+    "по",
+    "о",
+    "об",
+    "с",
+    "со",
+    "к",
+    "у",
+    "из",
+    "за",
+    "от",
+    "до",
+    "для",
+    "при",
+    "и",
+    "а",
+    "но",
+    "не",
+    "как",
+    "что",
+    "это",
+    "его",
+    "ее",
+    "её",
+    "их",
+    "так",
+    "также",
+    "когда",
+    "вчера",
+    "сегодня",
+    "завтра",
+    "после",
+    "однако",
+];
+
+/// Извлечение имен правилами, без внешнего сервиса.
+///
+/// Персоны (`PER`) – последовательности из двух-трех слов с заглавной буквы и
+/// фамилии с инициалами (`И.И. Иванов`, `Иванов И.И.`). Организации (`ORG`) – названия
+/// в кавычках, вместе с организационно-правовой формой перед ними (`фонд «Свобода»`),
+/// и последовательности латинских слов с заглавной буквы. Нормальная форма не
+/// вычисляется: `norm_name` совпадает с `name`
+pub struct RuleEntities {
+    person: Regex,
+    latin: Regex,
+}
+
+impl RuleEntities {
+    pub fn new() -> Self {
+        let word = r"[А-ЯЁ][а-яё]+(?:-[А-ЯЁ][а-яё]+)?";
+        let initials = r"[А-ЯЁ]\.\s?(?:[А-ЯЁ]\.\s?)?";
+        let person = format!(
+            r"{initials}{word}|{word}\s+[А-ЯЁ]\.\s?(?:[А-ЯЁ]\.)?|{word}(?:\s+{word}){{1,2}}"
+        );
+        RuleEntities {
+            person: Regex::new(&person).unwrap(),
+            latin: Regex::new(r"[A-Z][A-Za-z]+(?:\s+[A-Z][A-Za-z]+){1,3}").unwrap(),
+        }
+    }
+
+    /// Все найденные имена: сначала организации, затем персоны вне названий организаций.
+    /// Повторы одного имени возвращаются один раз с первым контекстом
+    pub fn extract(&self, text: &str) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        let mut seen = HashSet::new();
+        let mut push = |name: String, entity_type: &str, range: Range<usize>| {
+            if seen.insert((name.clone(), entity_type.to_string())) {
+                entities.push(Entity {
+                    norm_name: name.clone(),
+                    name,
+                    entity_type: entity_type.to_string(),
+                    context: text_context(text, range),
+                });
+            }
+        };
+
+        let quoted = quoted_spans(text);
+        for span in &quoted {
+            let inner = text[span.clone()]
+                .trim_start_matches(['«', '"'])
+                .trim_end_matches(['»', '"'])
+                .trim();
+            if inner.is_empty() {
+                continue;
+            }
+            let start = legal_form_start(text, span.start).unwrap_or(span.start);
+            let name = if start < span.start {
+                text[start..span.end].to_string()
+            } else {
+                inner.to_string()
+            };
+            push(name, "ORG", start..span.end);
+        }
+
+        let outside = |m: &regex::Match| {
+            !quoted
+                .iter()
+                .any(|q| q.start < m.end() && m.start() < q.end)
+        };
+
+        for m in self.person.find_iter(text).filter(outside) {
+            let after_stop_word = text[..m.start()]
+                .split_whitespace()
+                .next_back()
+                .is_some_and(is_stop_word);
+            let (offset, name) = strip_stop_words(m.as_str(), after_stop_word);
+            let is_initials = name.contains('.');
+            if name.split_whitespace().count() < 2 && !is_initials {
+                continue;
+            }
+            let start = m.start() + offset;
+            push(name.to_string(), "PER", start..m.end());
+        }
+
+        for m in self.latin.find_iter(text).filter(outside) {
+            push(m.as_str().to_string(), "ORG", m.range());
+        }
+
+        entities
+    }
+}
+
+impl Entities for RuleEntities {
+    async fn get_entities(&self, text: &str) -> Result<Response, ServiceError> {
+        Ok(Response {
+            entities: self.extract(text),
+        })
+    }
+}
+
+/// Диапазоны названий в кавычках вместе с кавычками. Елочки могут быть вложенными:
+/// `«Центр «Досье»»` – один диапазон. Прямые кавычки считаются парами
+pub fn quoted_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut depth = 0;
+    let mut open = 0;
+    let mut last_close = 0;
+    let mut straight: Option<usize> = None;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '«' => {
+                if depth == 0 {
+                    open = i;
+                }
+                depth += 1;
+            }
+            '»' if depth > 0 => {
+                depth -= 1;
+                last_close = i + c.len_utf8();
+                if depth == 0 {
+                    spans.push(open..i + c.len_utf8());
+                }
+            }
+            '"' if depth == 0 => match straight.take() {
+                Some(start) => spans.push(start..i + 1),
+                None => straight = Some(i),
+            },
+            _ => {}
+        }
+    }
+
+    // Незакрытая внешняя елочка при вложенных: `«Центр «Досье»` в реестре встречается
+    if depth > 0 && last_close > open {
+        spans.push(open..last_close);
+    }
+    spans
+}
+
+/// Начало организационно-правовой формы прямо перед кавычкой, если она есть
+fn legal_form_start(text: &str, quote: usize) -> Option<usize> {
+    let before = text[..quote].trim_end();
+    let word_start = before
+        .rfind(|c: char| c.is_whitespace() || c == '(' || c == ',')
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let word = before[word_start..].to_lowercase();
+    LEGAL_FORMS.contains(&word.as_str()).then_some(word_start)
+}
+
+/// Удаление служебных слов в начале совпадения: `Вчера Иван Иванов` -> `Иван Иванов`.
+/// После служебного слова (в том числе перед совпадением) из трех слов отбрасывается и первое: `В Москве Иван Иванов` -> `Иван Иванов`.
+/// Возвращает смещение оставшейся части в байтах
+fn strip_stop_words(name: &str, after_stop_word: bool) -> (usize, &str) {
+    let mut offset = 0;
+    let mut rest = name;
+    let mut stripped = after_stop_word;
+    while let Some((first, tail)) = rest.split_once(char::is_whitespace) {
+        let drop_noun = stripped && rest.split_whitespace().count() > 2;
+        if !drop_noun && !is_stop_word(first) {
+            break;
+        }
+        stripped = true;
+        let tail = tail.trim_start();
+        offset += rest.len() - tail.len();
+        rest = tail;
+    }
+    (offset, rest)
+}
+
+fn is_stop_word(word: &str) -> bool {
+    STOP_WORDS.contains(&word.to_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Текст и найденные в нем имена с типами, по порядку
+    #[rustfmt::skip]
+    const CASES: [(&str, &[(&str, &str)]); 12] = [
+        ("Об этом рассказал Иван Иванов.", &[("Иван Иванов", "PER")]),
+        ("Иванов Иван Иванович выступил с заявлением", &[("Иванов Иван Иванович", "PER")]),
+        ("Как сообщил И.И. Иванов, решение принято", &[("И.И. Иванов", "PER")]),
+        ("Заявление подписал И. Иванов", &[("И. Иванов", "PER")]),
+        ("Документ подписал Иванов И.И. вчера", &[("Иванов И.И.", "PER")]),
+        ("Вчера Иван Иванов приехал", &[("Иван Иванов", "PER")]),
+        ("В Москве Иван Иванов выступил", &[("Иван Иванов", "PER")]),
+        ("Москва приняла решение", &[]),
+        ("Учредитель ООО «Ромашка» и АНО «Центр «Досье»»", &[("ООО «Ромашка»", "ORG"), ("АНО «Центр «Досье»»", "ORG")]),
+        ("Сотрудники фонд «Свобода» и \"Медуза\"", &[("фонд «Свобода»", "ORG"), ("Медуза", "ORG")]),
+        ("По данным «Новой газеты» Петр Петров уехал", &[("Новой газеты", "ORG"), ("Петр Петров", "PER")]),
+        ("Издание The Insider опубликовало", &[("The Insider", "ORG")]),
+    ];
+
+    #[test]
+    fn names_and_organizations() {
+        let rules = RuleEntities::new();
+        for (text, expected) in CASES {
+            let found: Vec<(String, String)> = rules
+                .extract(text)
+                .into_iter()
+                .map(|e| (e.name, e.entity_type))
+                .collect();
+            let expected: Vec<(String, String)> = expected
+                .iter()
+                .map(|(n, t)| (n.to_string(), t.to_string()))
+                .collect();
+            assert_eq!(found, expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn repeated_names_keep_first_context() {
+        let filler = "и говорил очень долго ".repeat(10);
+        let text = format!("Иван Иванов пришел первым {filler}, после этого Иван Иванов ушел");
+        let entities = RuleEntities::new().extract(&text);
+        assert_eq!(entities.len(), 1, "{entities:?}");
+        assert!(entities[0].context.starts_with("Иван Иванов пришел"));
+        assert!(!entities[0].context.contains("ушел"));
+    }
+
+    #[test]
+    fn nested_quotes_are_one_span() {
+        let text = "«Центр «Досье»» и «Мемориал»";
+        let spans: Vec<&str> = quoted_spans(text).into_iter().map(|r| &text[r]).collect();
+        assert_eq!(spans, ["«Центр «Досье»»", "«Мемориал»"]);
+        let unclosed = "АНО «Центр «Досье» закрыта";
+        let spans: Vec<&str> = quoted_spans(unclosed)
+            .into_iter()
+            .map(|r| &unclosed[r])
+            .collect();
+        assert_eq!(spans, ["«Центр «Досье»"]);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::error::model::{ServiceError, UpstreamErrorKind};
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Настройки ограничителя, повторов и предохранителя для одного внешнего сервиса
+#[derive(Clone, Copy, Debug)]
+pub struct ResilienceConfig {
+    /// Сколько запросов в секунду пропускает ограничитель в среднем
+    pub rate_per_sec: f64,
+    /// Сколько запросов можно выполнить подряд без ожидания
+    pub burst: f64,
+    /// Всего попыток на один вызов, включая первую
+    pub max_attempts: u32,
+    /// Пауза перед первым повтором, дальше удваивается
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Сколько сбоев подряд размыкают предохранитель
+    pub failure_threshold: u32,
+    /// Через сколько разомкнутый предохранитель пропускает пробный запрос
+    pub open_timeout: Duration,
+    /// Сколько ждать установки соединения
+    pub connect_timeout: Duration,
+    /// Сколько ждать всего ответа одной попытки, включая соединение
+    pub request_timeout: Duration,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        ResilienceConfig {
+            rate_per_sec: 10.0,
+            burst: 10.0,
+            max_attempts: 3,
+            base_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            failure_threshold: 5,
+            open_timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ResilienceConfig {
+    /// Настройки из переменных `{prefix}_RPS`, `{prefix}_BURST`, `{prefix}_MAX_ATTEMPTS`,
+    /// `{prefix}_BREAKER_THRESHOLD`, `{prefix}_BREAKER_TIMEOUT_SECS`,
+    /// `{prefix}_CONNECT_TIMEOUT_SECS` и `{prefix}_TIMEOUT_SECS`.
+    /// Для отсутствующих переменных берутся значения по умолчанию
+    pub fn from_env(prefix: &str) -> Self {
+        Self::from_vars(prefix, |name| env::var(name).ok())
+    }
+
+    /// Настройки из переменных, которые возвращает `var`. Нечисловые и недопустимые
+    /// значения (`RPS` не больше нуля, `BURST` и `MAX_ATTEMPTS` меньше 1) заменяются
+    /// значениями по умолчанию с предупреждением
+    fn from_vars(prefix: &str, var: impl Fn(&str) -> Option<String>) -> Self {
+        let default = ResilienceConfig::default();
+        let value = |name: &str| {
+            let key = format!("{prefix}_{name}");
+            var(&key).map(|raw| (key, raw))
+        };
+        let secs = |name: &str, default: Duration| {
+            Duration::from_secs(checked(value(name), default.as_secs(), |_| true))
+        };
+        ResilienceConfig {
+            rate_per_sec: checked(value("RPS"), default.rate_per_sec, |v| {
+                v.is_finite() && v > 0.0
+            }),
+            burst: checked(value("BURST"), default.burst, |v| v.is_finite() && v >= 1.0),
+            max_attempts: checked(value("MAX_ATTEMPTS"), default.max_attempts, |v| v >= 1),
+            failure_threshold: checked(
+                value("BREAKER_THRESHOLD"),
+                default.failure_threshold,
+                |_| true,
+            ),
+            open_timeout: secs("BREAKER_TIMEOUT_SECS", default.open_timeout),
+            connect_timeout: secs("CONNECT_TIMEOUT_SECS", default.connect_timeout),
+            request_timeout: secs("TIMEOUT_SECS", default.request_timeout),
+            ..default
+        }
+    }
+}
+
+/// Значение переменной `(имя, строка)`, если оно разбирается и проходит `valid`,
+/// иначе `default`
+fn checked<T: FromStr + Copy + Display>(
+    value: Option<(String, String)>,
+    default: T,
+    valid: impl Fn(T) -> bool,
+) -> T {
+    let Some((key, raw)) = value else {
+        return default;
+    };
+    match raw.parse() {
+        Ok(v) if valid(v) => v,
+        _ => {
+            eprintln!("{key}={raw}: недопустимое значение, используется {default}");
+            default
+        }
+    }
+}
+
+/// Ошибка одной попытки вызова внешнего сервиса
+pub struct Failure {
+    pub message: String,
+    pub kind: FailureKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FailureKind {
+    /// Запрошенного объекта нет, например статьи на сайте
+    NotFound,
+    /// Сервис отклонил запрос (4xx) или вернул нечитаемый ответ
+    Rejected,
+    /// Ошибка сервиса (5xx)
+    Failed,
+    /// Сервис недоступен или превышена квота (ошибка соединения, 503, 429)
+    Unavailable,
+    /// Сервис не ответил вовремя
+    Timeout,
+}
+
+impl Failure {
+    pub fn not_found(message: String) -> Self {
+        Failure {
+            message,
+            kind: FailureKind::NotFound,
+        }
+    }
+
+    /// Ошибка по HTTP-статусу ответа
+    pub fn from_status(status: StatusCode, message: String) -> Self {
+        let kind = match status {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                FailureKind::Unavailable
+            }
+            StatusCode::GATEWAY_TIMEOUT => FailureKind::Timeout,
+            s if s.is_server_error() => FailureKind::Failed,
+            _ => FailureKind::Rejected,
+        };
+        Failure { message, kind }
+    }
+
+    /// Сбой сервиса: попытка повторяется и учитывается предохранителем.
+    /// Ошибка запроса не повторяется – сервис при этом работает
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self.kind,
+            FailureKind::Failed | FailureKind::Unavailable | FailureKind::Timeout
+        )
+    }
+
+    /// Ошибка вызова сервиса `upstream` для ответа API
+    pub fn into_error(self, upstream: &str) -> ServiceError {
+        let kind = match self.kind {
+            FailureKind::NotFound => return ServiceError::NotFound(self.message),
+            FailureKind::Rejected | FailureKind::Failed => UpstreamErrorKind::Failed,
+            FailureKind::Unavailable => UpstreamErrorKind::Unavailable,
+            FailureKind::Timeout => UpstreamErrorKind::Timeout,
+        };
+        ServiceError::Upstream {
+            upstream: upstream.to_string(),
+            kind,
+            message: self.message,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Failure {
+    fn from(e: reqwest::Error) -> Self {
+        let message = format!("{e}");
+        if e.is_timeout() {
+            return Failure {
+                message,
+                kind: FailureKind::Timeout,
+            };
+        }
+        if e.is_connect() {
+            return Failure {
+                message,
+                kind: FailureKind::Unavailable,
+            };
+        }
+        if let Some(status) = e.status() {
+            return Failure::from_status(status, message);
+        }
+        let kind = if e.is_decode() {
+            FailureKind::Rejected
+        } else {
+            FailureKind::Failed
+        };
+        Failure { message, kind }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Запросы проходят
+    Closed,
+    /// Запросы отклоняются без обращения к сервису
+    Open,
+    /// Пропускается один пробный запрос
+    HalfOpen,
+}
+
+/// Состояние внешнего сервиса для `/health`
+#[derive(Serialize)]
+pub struct UpstreamHealth {
+    pub name: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub available_tokens: f64,
+    pub calls: u64,
+    pub failures: u64,
+    /// Вызовы, отклоненные разомкнутым предохранителем
+    pub rejected: u64,
+}
+
+#[derive(Serialize)]
+pub struct HealthReport {
+    /// `ok`, если все предохранители замкнуты, иначе `degraded`
+    pub status: String,
+    pub upstreams: Vec<UpstreamHealth>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> ResilienceConfig {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (format!("TEST_{k}"), v.to_string()))
+            .collect();
+        ResilienceConfig::from_vars("TEST", |name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn valid_values_are_used() {
+        let c = config(&[
+            ("RPS", "2.5"),
+            ("BURST", "1"),
+            ("MAX_ATTEMPTS", "4"),
+            ("TIMEOUT_SECS", "7"),
+        ]);
+        assert_eq!(c.rate_per_sec, 2.5);
+        assert_eq!(c.burst, 1.0);
+        assert_eq!(c.max_attempts, 4);
+        assert_eq!(c.request_timeout, Duration::from_secs(7));
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_defaults() {
+        let default = ResilienceConfig::default();
+        #[rustfmt::skip]
+        const CASES: &[(&str, &str)] = &[
+            ("RPS", "0"), ("RPS", "-1"), ("RPS", "NaN"), ("RPS", "inf"), ("RPS", "x"),
+            ("BURST", "0.5"), ("BURST", "-3"), ("MAX_ATTEMPTS", "0"),
+        ];
+        for &(name, raw) in CASES {
+            let c = config(&[(name, raw)]);
+            assert_eq!(c.rate_per_sec, default.rate_per_sec, "{name}={raw}");
+            assert_eq!(c.burst, default.burst, "{name}={raw}");
+            assert_eq!(c.max_attempts, default.max_attempts, "{name}={raw}");
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use crate::error::model::{ServiceError, UpstreamErrorKind};
+use crate::resilience::model::{CircuitState, Failure, ResilienceConfig, UpstreamHealth};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{Instant, sleep};
+
+/// Внешний сервис за ограничителем запросов (token bucket), повторами
+/// с экспоненциальной паузой и предохранителем.
+///
+/// Клоны разделяют одно состояние и один HTTP-клиент, поэтому один `Upstream`
+/// можно отдать нескольким клиентам одного сервиса
+#[derive(Clone)]
+pub struct Upstream {
+    name: String,
+    config: Arc<ResilienceConfig>,
+    /// Клиент с таймаутами из `config`: зависший сервис дает сбой `Timeout`
+    client: reqwest::Client,
+    state: Arc<Mutex<UpstreamState>>,
+    /// Источник случайного разброса пауз между повторами
+    jitter: RandomState,
+}
+
+struct UpstreamState {
+    tokens: f64,
+    refilled_at: Instant,
+    circuit: CircuitState,
+    opened_at: Instant,
+    /// В полуразомкнутом состоянии пробный запрос уже выполняется.
+    /// Сбрасывается и при отмене пробного запроса, см. `ProbeGuard`
+    probe_in_flight: bool,
+    consecutive_failures: u32,
+    calls: u64,
+    failures: u64,
+    rejected: u64,
+}
+
+impl Upstream {
+    pub fn new(name: impl Into<String>, config: ResilienceConfig) -> Self {
+        let now = Instant::now();
+        // Как и `reqwest::Client::new()`, падает только если не инициализируется TLS
+        let client = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.request_timeout)
+            .build()
+            .expect("Не удалось создать HTTP-клиент");
+        Upstream {
+            name: name.into(),
+            config: Arc::new(config),
+            client,
+            state: Arc::new(Mutex::new(UpstreamState {
+                tokens: config.burst,
+                refilled_at: now,
+                circuit: CircuitState::Closed,
+                opened_at: now,
+                probe_in_flight: false,
+                consecutive_failures: 0,
+                calls: 0,
+                failures: 0,
+                rejected: 0,
+            })),
+            jitter: RandomState::new(),
+        }
+    }
+
+    /// HTTP-клиент для запросов к сервису
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Вызов сервиса: `op` выполняет одну попытку.
+    ///
+    /// Попытки со сбоем сервиса повторяются не больше `max_attempts` раз, пока
+    /// предохранитель замкнут. Разомкнутый предохранитель отклоняет вызов сразу
+    pub async fn call<T, F, Fut>(&self, mut op: F) -> Result<T, ServiceError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Failure>>,
+    {
+        let mut attempt = 0;
+        loop {
+            // Пробный запрос, брошенный на середине (клиент отключился), не должен
+            // оставлять предохранитель закрытым для всех следующих вызовов
+            let _probe = self.admit()?.then(|| ProbeGuard(&self.state));
+            self.acquire_token().await;
+
+            match op().await {
+                Ok(value) => {
+                    self.record_success();
+                    return Ok(value);
+                }
+                Err(failure) if !failure.retryable() => {
+                    // Сервис ответил, просто запрос неверный
+                    self.record_success();
+                    return Err(failure.into_error(&self.name));
+                }
+                Err(failure) => {
+                    let opened = self.record_failure();
+                    attempt += 1;
+                    if opened || attempt >= self.config.max_attempts.max(1) {
+                        return Err(failure.into_error(&self.name));
+                    }
+                    eprintln!(
+                        "{}: attempt {attempt} failed: {}",
+                        self.name, failure.message
+                    );
+                    sleep(self.backoff(attempt)).await;
+                }
+            }
+        }
+    }
+
+    pub fn health(&self) -> UpstreamHealth {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        UpstreamHealth {
+            name: self.name.clone(),
+            state: state.circuit,
+            consecutive_failures: state.consecutive_failures,
+            available_tokens: state.tokens,
+            calls: state.calls,
+            failures: state.failures,
+            rejected: state.rejected,
+        }
+    }
+
+    /// Проверка предохранителя перед попыткой. `true`, если попытка пробная
+    fn admit(&self) -> Result<bool, ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        let admitted = match state.circuit {
+            CircuitState::Closed => Some(false),
+            CircuitState::Open if state.opened_at.elapsed() >= self.config.open_timeout => {
+                state.circuit = CircuitState::HalfOpen;
+                state.probe_in_flight = true;
+                Some(true)
+            }
+            CircuitState::Open => None,
+            CircuitState::HalfOpen if state.probe_in_flight => None,
+            CircuitState::HalfOpen => {
+                state.probe_in_flight = true;
+                Some(true)
+            }
+        };
+
+        if let Some(probe) = admitted {
+            state.calls += 1;
+            Ok(probe)
+        } else {
+            state.rejected += 1;
+            Err(ServiceError::Upstream {
+                upstream: self.name.clone(),
+                kind: UpstreamErrorKind::Unavailable,
+                message: "предохранитель разомкнут".to_string(),
+            })
+        }
+    }
+
+    /// Ожидание свободного токена
+    async fn acquire_token(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                self.refill(&mut state);
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - state.tokens) / self.config.rate_per_sec)
+            };
+            sleep(wait).await;
+        }
+    }
+
+    fn refill(&self, state: &mut UpstreamState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.config.rate_per_sec).min(self.config.burst);
+        state.refilled_at = now;
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.circuit = CircuitState::Closed;
+        state.probe_in_flight = false;
+        state.consecutive_failures = 0;
+    }
+
+    /// Учет сбоя. `true`, если предохранитель разомкнут
+    fn record_failure(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        state.consecutive_failures += 1;
+        state.probe_in_flight = false;
+        if state.circuit == CircuitState::HalfOpen
+            || state.consecutive_failures >= self.config.failure_threshold
+        {
+            if state.circuit != CircuitState::Open {
+                eprintln!("{}: circuit opened", self.name);
+            }
+            state.circuit = CircuitState::Open;
+            state.opened_at = Instant::now();
+        }
+        state.circuit == CircuitState::Open
+    }
+
+    /// Пауза перед повтором `attempt`: `base_backoff * 2^(attempt - 1)`, не больше
+    /// `max_backoff`, со случайным разбросом от половины до полной величины
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .config
+            .base_backoff
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(self.config.max_backoff);
+        let random = self.jitter.hash_one(Instant::now()) as f64 / u64::MAX as f64;
+        exp.mul_f64(0.5 + random / 2.0)
+    }
+}
+
+/// Снимает отметку о пробном запросе, когда попытка завершена или отменена
+struct ProbeGuard<'a>(&'a Mutex<UpstreamState>);
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.0.lock() {
+            state.probe_in_flight = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resilience::model::FailureKind;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn upstream(config: ResilienceConfig) -> Upstream {
+        Upstream::new("test", config)
+    }
+
+    async fn fail(calls: &AtomicU32) -> Result<(), Failure> {
+        calls.fetch_add(1, Ordering::Relaxed);
+        Err(Failure {
+            message: "сбой".to_string(),
+            kind: FailureKind::Failed,
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn token_bucket_waits_for_refill() {
+        let upstream = upstream(ResilienceConfig {
+            rate_per_sec: 10.0,
+            burst: 2.0,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        upstream.acquire_token().await;
+        upstream.acquire_token().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        upstream.acquire_token().await;
+        let waited = start.elapsed();
+        assert!(waited >= Duration::from_millis(100), "{waited:?}");
+        assert!(waited < Duration::from_millis(110), "{waited:?}");
+
+        sleep(Duration::from_secs(10)).await;
+        assert_eq!(upstream.health().available_tokens, 2.0);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let upstream = upstream(ResilienceConfig {
+            base_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(1),
+            ..Default::default()
+        });
+        #[rustfmt::skip]
+        const CASES: &[(u32, u64)] = &[(1, 200), (2, 400), (3, 800), (4, 1000), (40, 1000)];
+        for &(attempt, full_ms) in CASES {
+            let pause = upstream.backoff(attempt);
+            let full = Duration::from_millis(full_ms);
+            assert!(pause >= full / 2 && pause <= full, "{attempt}: {pause:?}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn breaker_opens_probes_and_closes() {
+        let upstream = upstream(ResilienceConfig {
+            max_attempts: 1,
+            failure_threshold: 2,
+            open_timeout: Duration::from_secs(30),
+            ..Default::default()
+        });
+        let calls = AtomicU32::new(0);
+
+        assert!(upstream.call(|| fail(&calls)).await.is_err());
+        assert_eq!(upstream.health().state, CircuitState::Closed);
+        assert!(upstream.call(|| fail(&calls)).await.is_err());
+        assert_eq!(upstream.health().state, CircuitState::Open);
+
+        // Разомкнутый предохранитель не обращается к сервису
+        assert!(upstream.call(|| fail(&calls)).await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(upstream.health().rejected, 1);
+
+        // После паузы пропускается один пробный запрос, его сбой снова размыкает
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(upstream.admit().ok(), Some(true));
+        assert_eq!(upstream.health().state, CircuitState::HalfOpen);
+        assert!(upstream.admit().is_err());
+        assert!(upstream.record_failure());
+        assert_eq!(upstream.health().state, CircuitState::Open);
+
+        // Успешный пробный запрос замыкает предохранитель
+        sleep(Duration::from_secs(30)).await;
+        assert!(
+            upstream
+                .call(|| async { Ok::<_, Failure>(()) })
+                .await
+                .is_ok()
+        );
+        assert_eq!(upstream.health().state, CircuitState::Closed);
+        assert_eq!(upstream.health().consecutive_failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_attempts_are_retried() {
+        let upstream = upstream(ResilienceConfig {
+            max_attempts: 3,
+            failure_threshold: 10,
+            ..Default::default()
+        });
+        let calls = AtomicU32::new(0);
+        assert!(upstream.call(|| fail(&calls)).await.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        let calls = AtomicU32::new(0);
+        let rejected = upstream
+            .call(|| async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err::<(), _>(Failure::not_found("нет".to_string()))
+            })
+            .await;
+        assert!(matches!(rejected, Err(ServiceError::NotFound(_))));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
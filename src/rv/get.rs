@@ -1,26 +1,331 @@
+use crate::error::model::ServiceError;
+use crate::resilience::model::Failure;
+use crate::resilience::upstream::Upstream;
 use regex::Regex;
+use reqwest::StatusCode;
 use serde::Deserialize;
 use std::env;
+use std::sync::LazyLock;
 
 #[derive(Deserialize)]
 pub struct Text {
     text: String,
 }
 
-pub async fn get_text(id: &str) -> Result<String, reqwest::Error> {
-    let client = reqwest::Client::new();
-    let username = env::var("RVUSER").ok().unwrap();
-    let password = env::var("RVPASS").ok().unwrap();
-    let res = client
-        .get(format!("https://rtgazeta.ru/api/news/{id}"))
-        .basic_auth(username, Some(password))
-        .send()
+/// Текст статьи с сайта как есть, с HTML-разметкой. Запросы идут через `upstream`
+pub async fn get_html(id: &str, upstream: &Upstream) -> Result<String, ServiceError> {
+    let username = credential("RVUSER")?;
+    let password = credential("RVPASS")?;
+    let respose: Text = upstream
+        .call(|| async {
+            let client = upstream.client();
+            let res = client
+                .get(format!("https://rtgazeta.ru/api/news/{id}"))
+                .basic_auth(&username, Some(&password))
+                .send()
+                .await?;
+
+            let status = res.status();
+            if status == StatusCode::NOT_FOUND {
+                return Err(Failure::not_found(format!("Статья {id} не найдена")));
+            }
+            if !status.is_success() {
+                return Err(Failure::from_status(status, format!("rtgazeta {status}")));
+            }
+            Ok(res.json().await?)
+        })
         .await?;
-    let respose: Text = res.json().await?;
-    Ok(strip_html(respose.text))
+    Ok(respose.text)
+}
+
+/// Учетные данные сайта из переменной окружения `var`
+fn credential(var: &str) -> Result<String, ServiceError> {
+    env::var(var).map_err(|_| ServiceError::Internal(format!("Не задана переменная {var}")))
+}
+
+/// Тег или комментарий HTML. Имя тега – в первой группе
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<!--.*?-->|</?([a-zA-Z][a-zA-Z0-9]*)(?:\s[^<>]*)?/?>").unwrap()
+});
+static ENTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]+);").unwrap());
+
+/// Теги, которые разделяют абзацы или строки: на их месте в тексте `\n`
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+/// Текст без HTML-разметки и соответствие его смещений смещениям в исходном тексте.
+///
+/// Подряд идущие теги заменяются одним разделителем: `\n` для блочных тегов и
+/// пробелом для остальных между буквами, чтобы слова не слипались. Сущности
+/// (`&nbsp;`, `&laquo;`, `&#171;`) раскодируются. Текст без тегов остается как есть
+pub struct StrippedHtml {
+    pub text: String,
+    /// Замененные куски по порядку: теги и сущности. Между ними текст скопирован
+    replaced: Vec<Replaced>,
 }
 
-pub fn strip_html(input: String) -> String {
-    let re = Regex::new(r"<[^>]*>").unwrap();
-    re.replace_all(&input, "").to_string()
+/// Кусок `text` на месте куска исходного текста: `(начало, длина)` в каждом
+struct Replaced {
+    plain: (usize, usize),
+    source: (usize, usize),
+}
+
+impl StrippedHtml {
+    pub fn new(input: &str) -> Self {
+        let mut stripped = StrippedHtml {
+            text: String::with_capacity(input.len()),
+            replaced: Vec::new(),
+        };
+        if !TAG.is_match(input) {
+            stripped.text.push_str(input);
+            return stripped;
+        }
+
+        let mut last = 0;
+        let mut tags = TAG.captures_iter(input).peekable();
+        while let Some(tag) = tags.next() {
+            let whole = tag.get(0).unwrap();
+            stripped.push_text(&input[last..whole.start()], last);
+
+            // Теги вплотную друг к другу – один разделитель
+            let mut end = whole.end();
+            let mut block = is_block(&tag);
+            while let Some(next) = tags.next_if(|t| t.get(0).unwrap().start() == end) {
+                end = next.get(0).unwrap().end();
+                block |= is_block(&next);
+            }
+            let separator = if block { '\n' } else { ' ' };
+            let before = stripped.text.chars().next_back();
+            let after = input[end..].chars().next();
+            let needed = match (before, after) {
+                (None, _) | (_, None) => false,
+                (Some(b), Some(a)) if block => b != '\n' && a != '\n',
+                // Между словами; у знаков препинания разделитель не нужен: `«<i>Мемориал</i>»`
+                (Some(b), Some(a)) => b.is_alphanumeric() && a.is_alphanumeric(),
+            };
+            let inserted = if needed {
+                separator.encode_utf8(&mut [0; 4]).to_owned()
+            } else {
+                String::new()
+            };
+            stripped.push_replaced(&inserted, whole.start(), end);
+            last = end;
+        }
+        stripped.push_text(&input[last..], last);
+        stripped
+    }
+
+    /// Текст между тегами с раскодированными сущностями, `source` – его начало
+    fn push_text(&mut self, part: &str, source: usize) {
+        let mut last = 0;
+        for entity in ENTITY.captures_iter(part) {
+            let whole = entity.get(0).unwrap();
+            let Some(decoded) = decode_entity(&entity[1]) else {
+                continue;
+            };
+            self.text.push_str(&part[last..whole.start()]);
+            self.push_replaced(&decoded, source + whole.start(), source + whole.end());
+            last = whole.end();
+        }
+        self.text.push_str(&part[last..]);
+    }
+
+    fn push_replaced(&mut self, plain: &str, source_start: usize, source_end: usize) {
+        self.replaced.push(Replaced {
+            plain: (self.text.len(), plain.len()),
+            source: (source_start, source_end - source_start),
+        });
+        self.text.push_str(plain);
+    }
+
+    /// Смещение в байтах исходного текста для начала диапазона в `text`
+    pub fn source_start(&self, offset: usize) -> usize {
+        let i = self.replaced.partition_point(|r| r.plain.0 <= offset);
+        self.source(i, offset, |r| offset == r.plain.0)
+    }
+
+    /// Смещение в байтах исходного текста для конца диапазона в `text`:
+    /// тег сразу после диапазона в него не входит
+    pub fn source_end(&self, offset: usize) -> usize {
+        let i = self.replaced.partition_point(|r| r.plain.0 < offset);
+        self.source(i, offset, |_| false)
+    }
+
+    /// `replaced_before` – число замененных кусков, начатых до `offset`.
+    /// Внутри замены смещение переходит к ее началу, если `at_start`, иначе к концу
+    fn source(
+        &self,
+        replaced_before: usize,
+        offset: usize,
+        at_start: impl Fn(&Replaced) -> bool,
+    ) -> usize {
+        let Some(r) = replaced_before.checked_sub(1).map(|i| &self.replaced[i]) else {
+            return offset;
+        };
+        let (plain, plain_len) = r.plain;
+        let (source, source_len) = r.source;
+        if offset > plain + plain_len {
+            source + source_len + offset - plain - plain_len
+        } else if plain_len > 0 && at_start(r) {
+            source
+        } else {
+            source + source_len
+        }
+    }
+}
+
+fn is_block(tag: &regex::Captures) -> bool {
+    tag.get(1)
+        .is_some_and(|name| BLOCK_TAGS.contains(&name.as_str().to_ascii_lowercase().as_str()))
+}
+
+/// Символ сущности без `&` и `;`. Неизвестные сущности не раскодируются
+fn decode_entity(name: &str) -> Option<String> {
+    let code = if let Some(hex) = name
+        .strip_prefix(['#'])
+        .and_then(|n| n.strip_prefix(['x', 'X']))
+    {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(dec) = name.strip_prefix('#') {
+        dec.parse().ok()
+    } else {
+        None
+    };
+    let named = match (code, name) {
+        (Some(0xA0), _) | (None, "nbsp") => " ",
+        (Some(0xAD), _) | (None, "shy") => "",
+        (Some(code), _) => return char::from_u32(code).map(String::from),
+        (None, "amp") => "&",
+        (None, "lt") => "<",
+        (None, "gt") => ">",
+        (None, "quot") => "\"",
+        (None, "apos") => "'",
+        (None, "laquo") => "«",
+        (None, "raquo") => "»",
+        (None, "bdquo") => "„",
+        (None, "ldquo") => "“",
+        (None, "rdquo") => "”",
+        (None, "lsquo") => "‘",
+        (None, "rsquo") => "’",
+        (None, "ndash") => "–",
+        (None, "mdash") => "—",
+        (None, "hellip") => "…",
+        (None, "numero") => "№",
+        _ => return None,
+    };
+    Some(named.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_map_to_source() {
+        let html = "<p>Иван <b>Иванов</b></p><p>Мемориал</p>";
+        let stripped = StrippedHtml::new(html);
+        assert_eq!(stripped.text, "Иван Иванов\nМемориал");
+
+        let plain = |part: &str| stripped.text.find(part).unwrap();
+        let source = |part: &str| html.find(part).unwrap();
+        assert_eq!(stripped.source_start(0), source("Иван "));
+        // Начало диапазона на границе тега – после тега, конец – до него
+        assert_eq!(stripped.source_start(plain("Иванов")), source("Иванов"));
+        assert_eq!(stripped.source_end(plain("Иванов")), source("<b>"));
+        assert_eq!(stripped.source_end(plain("\n")), source("</b>"));
+        assert_eq!(stripped.source_start(plain("Мемориал")), source("Мемориал"));
+        assert_eq!(
+            stripped.source_end(stripped.text.len()),
+            html.rfind("</p>").unwrap()
+        );
+    }
+
+    #[test]
+    fn text_without_markup_is_unchanged() {
+        let stripped = StrippedHtml::new("Иван Иванов");
+        assert_eq!(stripped.text, "Иван Иванов");
+        assert_eq!(stripped.source_start(5), 5);
+        assert_eq!(stripped.source_end(20), 20);
+
+        // Угловые скобки в обычном тексте – не теги
+        let text = "a < b, c > d, x<y";
+        assert_eq!(StrippedHtml::new(text).text, text);
+    }
+
+    #[test]
+    fn tags_are_replaced_with_separators() {
+        #[rustfmt::skip]
+        const CASES: &[(&str, &str)] = &[
+            ("<p>Иванов</p><p>Мемориал</p>", "Иванов\nМемориал"),
+            ("Иванов<br/>Мемориал", "Иванов\nМемориал"),
+            ("<li>Иванов</li>\n<li>Мемориал</li>", "Иванов\nМемориал"),
+            ("<b>Иван</b><i>Иванов</i>", "Иван Иванов"),
+            ("<b>Иван </b><i>Иванов</i>", "Иван Иванов"),
+            ("Иван<!-- комментарий -->Иванов", "Иван Иванов"),
+            ("<span class=\"x\">Ив</span>", "Ив"),
+            ("«<i>Мемориал</i>»", "«Мемориал»"),
+        ];
+        for (html, text) in CASES {
+            assert_eq!(StrippedHtml::new(html).text, *text, "{html}");
+        }
+    }
+
+    #[test]
+    fn entities_are_decoded() {
+        let html = "<p>Иван&nbsp;Иванов и &laquo;Мемориал&raquo;, &quot;Щит&quot; &#8212; &amp;&unknown;</p>";
+        let stripped = StrippedHtml::new(html);
+        assert_eq!(
+            stripped.text,
+            "Иван Иванов и «Мемориал», \"Щит\" — &&unknown;"
+        );
+
+        let plain = |part: &str| stripped.text.find(part).unwrap();
+        let source = |part: &str| html.find(part).unwrap();
+        let start = plain("Иван");
+        let end = plain(" и");
+        assert_eq!(stripped.source_start(start), source("Иван"));
+        assert_eq!(stripped.source_end(end), source(" и"));
+        assert_eq!(stripped.source_start(plain("Мемориал")), source("Мемориал"));
+        assert_eq!(stripped.source_end(plain("»")), source("&raquo;"));
+        assert_eq!(stripped.source_end(plain(",")), source(","));
+        // Диапазон с кавычкой переходит на всю сущность
+        assert_eq!(stripped.source_start(plain("«")), source("&laquo;"));
+        assert_eq!(
+            stripped.source_end(plain("Мемориал") + "Мемориал»".len()),
+            source(",")
+        );
+    }
 }
@@ -1,6 +1,15 @@
+use regex::Regex;
 use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::LazyLock;
 use strsim::levenshtein;
 
+/// Сколько символов текста берется в контекст с каждой стороны от имени
+const CONTEXT_CHARS: usize = 100;
+
+/// Текст в кавычках `"..."` или `«...»`
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"["«]([^"«»]+)["»]"#).unwrap());
+
 pub fn cosine_similarity(v1: &[f32], v2: &[f32]) -> f32 {
     if v1.len() != v2.len() || v1.is_empty() || v2.is_empty() {
         return -1.0;
@@ -62,3 +71,55 @@ pub fn keep_russian_and_dot(input: &str) -> String {
         })
         .collect()
 }
+
+/// Извлекает псевдонимы из кавычек: `Иванов Иван "Ваня Рок, Dry Ice"` -> `["Ваня Рок", "Dry Ice"]`.
+///
+/// Если в кавычках записано все имя целиком (`"Револьт-Центр"`), псевдонимов нет
+pub fn extract_aliases(s: &str) -> Vec<String> {
+    let unquoted: String = s
+        .chars()
+        .filter(|c| !matches!(c, '"' | '«' | '»'))
+        .collect();
+
+    let mut aliases = Vec::new();
+    for cap in QUOTED.captures_iter(s) {
+        if cap[1].trim() == unquoted.trim() {
+            continue;
+        }
+        for alias in cap[1].split(',') {
+            let alias = alias.trim();
+            if !alias.is_empty() && !aliases.iter().any(|a| a == alias) {
+                aliases.push(alias.to_string());
+            }
+        }
+    }
+    aliases
+}
+
+/// Контекст имени: до `CONTEXT_CHARS` символов с каждой стороны по границам слов
+pub fn text_context(text: &str, range: Range<usize>) -> String {
+    let from = text[..range.start]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT_CHARS)
+        .map(|(i, _)| i);
+    let to = text[range.end..]
+        .char_indices()
+        .nth(CONTEXT_CHARS)
+        .map(|(i, _)| range.end + i);
+
+    // Обрезанное с краю слово отбрасывается целиком
+    let from = match from {
+        Some(i) => text[i..range.start]
+            .find(char::is_whitespace)
+            .map_or(i, |w| i + w),
+        None => 0,
+    };
+    let to = match to {
+        Some(i) => text[range.end..i]
+            .rfind(char::is_whitespace)
+            .map_or(i, |w| range.end + w),
+        None => text.len(),
+    };
+    text[from..to].trim().to_string()
+}
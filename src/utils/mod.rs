@@ -1 +1,3 @@
 pub mod funcs;
+pub mod morphology;
+pub mod translit;
@@ -0,0 +1,145 @@
+/// Ключ слова имени без падежного и родового окончания, в нижнем регистре и без `ё`.
+///
+/// Это не словарная форма, а основа для сравнения: все падежные формы одного имени
+/// и мужская и женская форма фамилии дают один ключ – `Иванову`, `Ивановой`,
+/// `Иванова` -> `иванов`, `Марией` -> `мар`. Части двойной фамилии обрабатываются
+/// отдельно, слова короче двух букв и инициалы не меняются
+pub fn name_stem(word: &str) -> String {
+    let word: String = word
+        .to_lowercase()
+        .chars()
+        .map(|c| if c == 'ё' { 'е' } else { c })
+        .collect();
+    word.split('-')
+        .map(strip_ending)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Ключи всех слов имени через пробел: `Ивановой-Смирновой М.П.` -> `иванов-смирнов м.п.`
+pub fn normalize_name(name: &str) -> String {
+    name.split_whitespace()
+        .map(name_stem)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Окончания падежей и рода: прилагательные фамилии (`-ого`, `-ая`), фамилии на
+/// `-ов`/`-ин` (`-ым`, `-ой`), имена на `-ий`/`-ия`/`-ья`/`-ей`/`-ай`, на `-а` и на
+/// согласный или `-ь`. Отбрасывается самое длинное подходящее
+const ENDINGS: [&str; 48] = [
+    "ыми", "ими", "ого", "его", "ому", "ему", "ией", "ием", "ьей", "еем", "аем", "ых", "их", "ой",
+    "ей", "ою", "ею", "ым", "им", "ом", "ем", "ая", "яя", "ую", "юю", "ий", "ый", "ия", "ии", "ию",
+    "ья", "ьи", "ью", "ье", "ея", "ее", "ай", "аю", "ае", "а", "я", "ы", "и", "у", "ю", "е", "ь",
+    "й",
+];
+/// Основа после отбрасывания окончания не короче
+const MIN_STEM_CHARS: usize = 2;
+
+fn strip_ending(word: &str) -> String {
+    if word.ends_with('.') || !word.chars().all(|c| matches!(c, 'а'..='я')) {
+        return word.to_string();
+    }
+    let chars = word.chars().count();
+    ENDINGS
+        .iter()
+        .filter(|e| word.ends_with(*e))
+        .map(|e| e.chars().count())
+        .filter(|len| chars - len >= MIN_STEM_CHARS)
+        .max()
+        .map(|len| word.chars().take(chars - len).collect())
+        .unwrap_or_else(|| word.to_string())
+}
+
+/// Падежные формы фамилии в нижнем регистре вместе с начальной.
+///
+/// Покрывают типичные русские фамилии: на -ов/-ин, -ский/-ый/-ой, на согласный и -ь.
+/// Женские фамилии на согласный не склоняются
+pub fn surname_forms(surname: &str, is_female: Option<bool>) -> Vec<String> {
+    let is_female = is_female.unwrap_or(surname.ends_with('а') || surname.ends_with("ая"));
+    let replace = |cut: usize, endings: &[&str]| -> Vec<String> {
+        let stem: String = {
+            let chars: Vec<char> = surname.chars().collect();
+            chars[..chars.len() - cut].iter().collect()
+        };
+        endings.iter().map(|e| format!("{stem}{e}")).collect()
+    };
+    let ends_with_any = |endings: &[&str]| endings.iter().any(|e| surname.ends_with(e));
+
+    let mut forms = vec![surname.to_string()];
+    forms.extend(if is_female {
+        if ends_with_any(&["ова", "ева", "ина", "ына"]) {
+            replace(1, &["ой", "у"])
+        } else if surname.ends_with("ая") {
+            replace(2, &["ой", "ую"])
+        } else if surname.ends_with('а') {
+            replace(1, &["ы", "е", "у", "ой"])
+        } else {
+            Vec::new()
+        }
+    } else if ends_with_any(&["ов", "ев", "ин", "ын"]) {
+        replace(0, &["а", "у", "ым", "е"])
+    } else if ends_with_any(&["ый", "ой"]) {
+        replace(2, &["ого", "ому", "ым", "ом"])
+    } else if surname.ends_with("ий") {
+        replace(2, &["ого", "ому", "им", "ом"])
+    } else if ends_with_any(&["ь", "й"]) {
+        replace(1, &["я", "ю", "ем", "е"])
+    } else if surname.ends_with('а') {
+        replace(1, &["ы", "е", "у", "ой"])
+    } else if surname.chars().last().is_some_and(is_consonant) {
+        replace(0, &["а", "у", "ом", "е"])
+    } else {
+        Vec::new()
+    });
+    forms
+}
+
+fn is_consonant(c: char) -> bool {
+    "бвгджзклмнпрстфхцчшщ".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ключ и формы в шести падежах: именительный, родительный, дательный,
+    /// винительный, творительный, предложный
+    #[rustfmt::skip]
+    const CASES: [(&str, [&str; 6]); 16] = [
+        ("иванов", ["Иванов", "Иванова", "Иванову", "Иванова", "Ивановым", "Иванове"]),
+        ("иванов", ["Иванова", "Ивановой", "Ивановой", "Иванову", "Ивановой", "Ивановой"]),
+        ("путин", ["Путин", "Путина", "Путину", "Путина", "Путиным", "Путине"]),
+        ("достоевск", ["Достоевский", "Достоевского", "Достоевскому", "Достоевского", "Достоевским", "Достоевском"]),
+        ("достоевск", ["Достоевская", "Достоевской", "Достоевской", "Достоевскую", "Достоевской", "Достоевской"]),
+        ("навальн", ["Навальный", "Навального", "Навальному", "Навального", "Навальным", "Навальном"]),
+        ("иванов-смирнов", ["Иванова-Смирнова", "Ивановой-Смирновой", "Ивановой-Смирновой", "Иванову-Смирнову", "Ивановой-Смирновой", "Ивановой-Смирновой"]),
+        ("борухович", ["Борухович", "Борухович", "Борухович", "Борухович", "Борухович", "Борухович"]),
+        ("иван", ["Иван", "Ивана", "Ивану", "Ивана", "Иваном", "Иване"]),
+        ("алекс", ["Алексей", "Алексея", "Алексею", "Алексея", "Алексеем", "Алексее"]),
+        ("дмитр", ["Дмитрий", "Дмитрия", "Дмитрию", "Дмитрия", "Дмитрием", "Дмитрии"]),
+        ("игор", ["Игорь", "Игоря", "Игорю", "Игоря", "Игорем", "Игоре"]),
+        ("мар", ["Мария", "Марии", "Марии", "Марию", "Марией", "Марии"]),
+        ("натал", ["Наталья", "Натальи", "Наталье", "Наталью", "Натальей", "Наталье"]),
+        ("петрович", ["Петрович", "Петровича", "Петровичу", "Петровича", "Петровичем", "Петровиче"]),
+        ("петровн", ["Петровна", "Петровны", "Петровне", "Петровну", "Петровной", "Петровне"]),
+    ];
+
+    #[test]
+    fn all_cases_give_one_stem() {
+        for (stem, forms) in CASES {
+            for form in forms {
+                assert_eq!(name_stem(form), stem, "{form}");
+            }
+        }
+    }
+
+    #[test]
+    fn full_names_and_initials() {
+        assert_eq!(
+            normalize_name("Ивановой Марии Петровны"),
+            normalize_name("Иванова Мария Петровна")
+        );
+        assert_eq!(normalize_name("Семёнову И.И."), "семенов и.и.");
+    }
+}
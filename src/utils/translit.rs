@@ -0,0 +1,310 @@
+/// Система транслитерации кириллицы латиницей
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scheme {
+    /// BGN/PCGN с английскими окончаниями имен, как пишут в прессе:
+    /// `Навальный` -> `navalny`, `Ходорковский` -> `khodorkovsky`
+    Bgn,
+    /// ГОСТ 7.79-2000, система Б, без апострофов у `ъ`, `ы`, `ь` и `э`:
+    /// `Навальный` -> `navalnyj`, `Ходорковский` -> `xodorkovskij`
+    Gost,
+}
+
+impl Scheme {
+    pub const ALL: [Scheme; 2] = [Scheme::Bgn, Scheme::Gost];
+}
+
+/// Транслитерация кириллицы латиницей по системе `scheme`: `Медуза` -> `meduza`.
+///
+/// Результат в нижнем регистре, `ь` и `ъ` опускаются
+pub fn to_latin(text: &str, scheme: Scheme) -> String {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    let mut out = String::with_capacity(text.len());
+
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let next = chars.get(i + 1).copied();
+        let word_start = !prev.is_some_and(char::is_alphabetic);
+        let word_end = !next.is_some_and(char::is_alphabetic);
+
+        let latin = match (scheme, c) {
+            (Scheme::Gost, 'ё') => "yo",
+            (Scheme::Gost, 'й') => "j",
+            (Scheme::Gost, 'х') => "x",
+            // `c` перед i, e, y, j, иначе `cz`
+            (Scheme::Gost, 'ц') if next.is_some_and(|n| "иеёыйэюя".contains(n)) => "c",
+            (Scheme::Gost, 'ц') => "cz",
+            (Scheme::Gost, 'щ') => "shh",
+            // Окончания -ый/-ий пишутся одной `y`: Navalny, Khodorkovsky
+            (Scheme::Bgn, 'ы' | 'и')
+                if next == Some('й') && !chars.get(i + 2).is_some_and(|c| c.is_alphabetic()) =>
+            {
+                "y"
+            }
+            (Scheme::Bgn, 'й') if matches!(prev, Some('ы' | 'и')) && word_end => "",
+            // В начале имени `е` пишется как в прессе: Elena, Evgeny
+            (Scheme::Bgn, 'е') if prev.is_some_and(is_cyrillic_vowel) => "ye",
+            (Scheme::Bgn, 'ё') if word_start || prev.is_some_and(is_cyrillic_vowel) => "yo",
+            (_, 'а') => "a",
+            (_, 'б') => "b",
+            (_, 'в') => "v",
+            (_, 'г') => "g",
+            (_, 'д') => "d",
+            (_, 'е') => "e",
+            (_, 'ё') => "e",
+            (_, 'ж') => "zh",
+            (_, 'з') => "z",
+            (_, 'и') => "i",
+            (_, 'й') => "y",
+            (_, 'к') => "k",
+            (_, 'л') => "l",
+            (_, 'м') => "m",
+            (_, 'н') => "n",
+            (_, 'о') => "o",
+            (_, 'п') => "p",
+            (_, 'р') => "r",
+            (_, 'с') => "s",
+            (_, 'т') => "t",
+            (_, 'у') => "u",
+            (_, 'ф') => "f",
+            (_, 'х') => "kh",
+            (_, 'ц') => "ts",
+            (_, 'ч') => "ch",
+            (_, 'ш') => "sh",
+            (_, 'щ') => "shch",
+            (_, 'ъ' | 'ь') => "",
+            (_, 'ы') => "y",
+            (_, 'э') => "e",
+            (_, 'ю') => "yu",
+            (_, 'я') => "ya",
+            _ => {
+                out.push(c);
+                continue;
+            }
+        };
+        out.push_str(latin);
+    }
+    out
+}
+
+/// Обратная транслитерация латиницы кириллицей.
+///
+/// Понимает обе системы `Scheme`: BGN/PCGN (`kh`, `ts`, `shch`, `ya`) и ГОСТ 7.79
+/// (`x`, `cz`, `shh`, `j` после гласной), а также английские написания (`ck`, `ph`,
+/// `x` внутри слова -> `кс`). Конечная `y` после согласного читается как `-ый`/`-ий`:
+/// `Navalny` -> `Навалный`, `Dmitry` -> `Дмитрий`. Заглавная буква в начале слова
+/// сохраняется
+pub fn to_cyrillic(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphabetic() {
+            word.push(c);
+        } else {
+            out.push_str(&word_to_cyrillic(&word));
+            word.clear();
+            out.push(c);
+        }
+    }
+    out.push_str(&word_to_cyrillic(&word));
+    out
+}
+
+/// Сочетания латинских букв, от длинных к коротким
+const DIGRAPHS: [(&str, &str); 24] = [
+    ("shch", "щ"),
+    ("sch", "щ"),
+    ("shh", "щ"),
+    ("zh", "ж"),
+    ("kh", "х"),
+    ("ts", "ц"),
+    ("tz", "ц"),
+    ("cz", "ц"),
+    ("ch", "ч"),
+    ("sh", "ш"),
+    ("yu", "ю"),
+    ("ju", "ю"),
+    ("iu", "ию"),
+    ("ia", "ия"),
+    ("ya", "я"),
+    ("ja", "я"),
+    ("yo", "ё"),
+    ("jo", "ё"),
+    ("ye", "е"),
+    ("ck", "к"),
+    ("ph", "ф"),
+    ("th", "т"),
+    ("ee", "и"),
+    ("oo", "у"),
+];
+
+fn word_to_cyrillic(word: &str) -> String {
+    if word.is_empty() {
+        return String::new();
+    }
+    let lower = word.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    let mut out = String::with_capacity(word.len() * 2);
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if let Some((latin, cyrillic)) = DIGRAPHS.iter().find(|(l, _)| lower[i..].starts_with(l)) {
+            out.push_str(cyrillic);
+            i += latin.len();
+            continue;
+        }
+
+        let c = bytes[i] as char;
+        let next = bytes.get(i + 1).map(|b| *b as char);
+        let cyrillic = match c {
+            'y' if i + 1 == bytes.len() && i > 0 => {
+                let prev = bytes[i - 1] as char;
+                if is_latin_vowel(prev) {
+                    "й"
+                } else if matches!(prev, 'k' | 'g' | 'h' | 'r' | 'l') {
+                    "ий"
+                } else {
+                    "ый"
+                }
+            }
+            'y' if next.is_some_and(is_latin_vowel) => "й",
+            // ГОСТ: `j` после гласной – `й` (Sergej, Navalnyj), иначе английское `дж`
+            'j' if i > 0
+                && "aeiouy".contains(bytes[i - 1] as char)
+                && !next.is_some_and(is_latin_vowel) =>
+            {
+                "й"
+            }
+            // ГОСТ: `x` в начале слова – `х` (Xodorkovskij), внутри – английское `кс`
+            'x' if i == 0 => "х",
+            'y' => "ы",
+            'c' if next.is_some_and(|n| matches!(n, 'e' | 'i' | 'y')) => "ц",
+            'a' => "а",
+            'b' => "б",
+            'c' | 'k' | 'q' => "к",
+            'd' => "д",
+            'e' => "е",
+            'f' => "ф",
+            'g' => "г",
+            'h' => "х",
+            'i' => "и",
+            'j' => "дж",
+            'l' => "л",
+            'm' => "м",
+            'n' => "н",
+            'o' => "о",
+            'p' => "п",
+            'r' => "р",
+            's' => "с",
+            't' => "т",
+            'u' => "у",
+            'v' | 'w' => "в",
+            'x' => "кс",
+            'z' => "з",
+            _ => "",
+        };
+        out.push_str(cyrillic);
+        i += 1;
+    }
+
+    if word.starts_with(|c: char| c.is_ascii_uppercase()) {
+        let mut chars = out.chars();
+        if let Some(first) = chars.next() {
+            return first.to_uppercase().chain(chars).collect();
+        }
+    }
+    out
+}
+
+fn is_cyrillic_vowel(c: char) -> bool {
+    "аеёиоуыэюяьъ".contains(c)
+}
+
+fn is_latin_vowel(c: char) -> bool {
+    "aeiou".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const TO_LATIN: [(&str, &str); 10] = [
+        ("Навальный", "navalny"),
+        ("Ходорковский", "khodorkovsky"),
+        ("Медуза", "meduza"),
+        ("Дмитрий", "dmitry"),
+        ("Алексей Анатольевич", "aleksey anatolyevich"),
+        ("Елена", "elena"),
+        ("Евгений", "evgeny"),
+        ("Артём Ёлкин", "artem yolkin"),
+        ("Щукин Тарасович", "shchukin tarasovich"),
+        ("Объединённая Россия", "obyedinennaya rossiya"),
+    ];
+
+    #[rustfmt::skip]
+    const TO_LATIN_GOST: [(&str, &str); 8] = [
+        ("Навальный", "navalnyj"),
+        ("Ходорковский", "xodorkovskij"),
+        ("Алексей Анатольевич", "aleksej anatolevich"),
+        ("Артём Ёлкин", "artyom yolkin"),
+        ("Щукин", "shhukin"),
+        ("Цой", "czoj"),
+        ("Цыганов Лицей", "cyganov licej"),
+        ("Объединённая Россия", "obedinyonnaya rossiya"),
+    ];
+
+    /// Слова без `ь` и `ъ`, которые транслитерация опускает
+    #[rustfmt::skip]
+    const ROUND_TRIP: [&str; 9] = [
+        "Ходорковский", "Сергей Смирнов", "Юлия", "Щукин", "Цой", "Журавлев",
+        "Чечня", "Алексей", "Медуза",
+    ];
+
+    #[rustfmt::skip]
+    const TO_CYRILLIC: [(&str, &str); 13] = [
+        ("Navalny", "Навалный"),
+        ("Khodorkovsky", "Ходорковский"),
+        ("Dmitry", "Дмитрий"),
+        ("Meduza", "Медуза"),
+        ("Sergey Smirnov", "Сергей Смирнов"),
+        ("Yulia", "Юлия"),
+        ("Shchukin", "Щукин"),
+        ("Czar", "Цар"),
+        ("Alexander", "Александер"),
+        ("Philip", "Филип"),
+        ("Xodorkovskij", "Ходорковский"),
+        ("Sergej Navalnyj", "Сергей Навалный"),
+        ("Jim Beam", "Джим Беам"),
+    ];
+
+    #[test]
+    fn cyrillic_to_latin() {
+        for (cyrillic, latin) in TO_LATIN {
+            assert_eq!(to_latin(cyrillic, Scheme::Bgn), latin, "{cyrillic}");
+        }
+        for (cyrillic, latin) in TO_LATIN_GOST {
+            assert_eq!(to_latin(cyrillic, Scheme::Gost), latin, "{cyrillic}");
+        }
+    }
+
+    #[test]
+    fn round_trip_in_each_scheme() {
+        for scheme in Scheme::ALL {
+            for word in ROUND_TRIP {
+                let latin = to_latin(word, scheme);
+                assert_eq!(
+                    to_cyrillic(&latin),
+                    word.to_lowercase(),
+                    "{scheme:?}: {latin}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn latin_to_cyrillic() {
+        for (latin, cyrillic) in TO_CYRILLIC {
+            assert_eq!(to_cyrillic(latin), cyrillic, "{latin}");
+        }
+    }
+}